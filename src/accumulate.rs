@@ -0,0 +1,105 @@
+use std::{error::Error, fmt};
+use crate::big_uint::BigUint;
+
+/// ## Values a reduce can add up or multiply, each way of dealing with a result which doesn't fit.
+/// Implemented for the integers, where it's their own `checked_*`, `saturating_*` and `wrapping_*` methods,
+/// and for [`BigUint`], which always fits.
+pub trait Accumulate: Sized {
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn saturating_add(self, other: Self) -> Self;
+    fn saturating_mul(self, other: Self) -> Self;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+}
+
+macro_rules! accumulate_integers {
+    ($($integer:ty),*) => {$(
+        impl Accumulate for $integer {
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$integer>::checked_add(self, other)
+            }
+            fn checked_mul(self, other: Self) -> Option<Self> {
+                <$integer>::checked_mul(self, other)
+            }
+            fn saturating_add(self, other: Self) -> Self {
+                <$integer>::saturating_add(self, other)
+            }
+            fn saturating_mul(self, other: Self) -> Self {
+                <$integer>::saturating_mul(self, other)
+            }
+            fn wrapping_add(self, other: Self) -> Self {
+                <$integer>::wrapping_add(self, other)
+            }
+            fn wrapping_mul(self, other: Self) -> Self {
+                <$integer>::wrapping_mul(self, other)
+            }
+        }
+    )*};
+}
+
+accumulate_integers!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl Accumulate for BigUint {
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Some(self + other)
+    }
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Some(self * other)
+    }
+    fn saturating_add(self, other: Self) -> Self {
+        self + other
+    }
+    fn saturating_mul(self, other: Self) -> Self {
+        self * other
+    }
+    fn wrapping_add(self, other: Self) -> Self {
+        self + other
+    }
+    fn wrapping_mul(self, other: Self) -> Self {
+        self * other
+    }
+}
+
+/// ### What a sum or product does when it doesn't fit its type.
+/// - `Wrapping`: starts over from the bottom, what `+` does in a release build (and panics in a debug build).
+/// - `Checked`: fails with [`Overflowed`].
+/// - `Saturating`: stays at the largest (or smallest) value there is. For unsigned values, a saturated sum of
+///   saturated partial sums is the saturated total, so it doesn't matter how the values are grouped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    Wrapping,
+    Checked,
+    Saturating,
+}
+
+impl Arithmetic {
+
+    pub fn add<V: Accumulate>(self, a: V, b: V) -> Result<V, Overflowed> {
+        match self {
+            Arithmetic::Wrapping => Ok(a.wrapping_add(b)),
+            Arithmetic::Checked => a.checked_add(b).ok_or(Overflowed),
+            Arithmetic::Saturating => Ok(a.saturating_add(b)),
+        }
+    }
+
+    pub fn mul<V: Accumulate>(self, a: V, b: V) -> Result<V, Overflowed> {
+        match self {
+            Arithmetic::Wrapping => Ok(a.wrapping_mul(b)),
+            Arithmetic::Checked => a.checked_mul(b).ok_or(Overflowed),
+            Arithmetic::Saturating => Ok(a.saturating_mul(b)),
+        }
+    }
+}
+
+/// A [`Arithmetic::Checked`] sum or product which didn't fit its type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflowed;
+
+impl fmt::Display for Overflowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "arithmetic overflow")
+    }
+}
+
+impl Error for Overflowed {}
@@ -0,0 +1,226 @@
+use std::{cmp::Ordering, error::Error, fmt, io::{self, Read, Write}, iter::{Product, Sum}, ops::{Add, AddAssign, Mul, MulAssign}, str::FromStr};
+use crate::spill::Spill;
+
+/// Every limb is a number below `BASE`, nine decimal digits.
+const BASE: u32 = 1_000_000_000;
+const BASE_DIGITS: usize = 9;
+
+/// ## An unsigned integer as big as memory allows.
+/// Stored in base 10^9, least significant limb first, so it converts to and from decimal strings without any division.
+/// Adding and multiplying never overflow, which makes it the accumulator for sums and products which outgrow `u128`,
+/// see [`MapReduce::try_sum`](crate::map_reduce::MapReduce::try_sum).
+/// ```ignore
+/// let big = "18446744073709551615".parse::<BigUint>()? + BigUint::from(1_u8);
+/// assert_eq!((&big * &big).to_string(), "340282366920938463463374607431768211456");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    /// No zero limbs at the end, zero has none at all.
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn trimmed(mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigUint { limbs }
+    }
+}
+
+macro_rules! big_uint_from {
+    ($($unsigned:ty),*) => {$(
+        impl From<$unsigned> for BigUint {
+            fn from(value: $unsigned) -> Self {
+                let mut value = value as u128;
+                let mut limbs = Vec::new();
+                while value > 0 {
+                    limbs.push((value % BASE as u128) as u32);
+                    value /= BASE as u128;
+                }
+                BigUint { limbs }
+            }
+        }
+    )*};
+}
+
+big_uint_from!(u8, u16, u32, u64, u128, usize);
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs.len().cmp(&other.limbs.len()).then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+        let mut carry = 0;
+        for (position, limb) in self.limbs.iter_mut().enumerate() {
+            let sum = *limb + other.limbs.get(position).copied().unwrap_or(0) + carry;
+            (*limb, carry) = (sum % BASE, sum / BASE);
+            // nothing left to add past the end of `other`
+            if carry == 0 && position >= other.limbs.len() {
+                break;
+            }
+        }
+        if carry > 0 {
+            self.limbs.push(carry);
+        }
+    }
+}
+
+impl AddAssign for BigUint {
+    fn add_assign(&mut self, other: BigUint) {
+        *self += &other;
+    }
+}
+
+impl Add<&BigUint> for &BigUint {
+    type Output = BigUint;
+    fn add(self, other: &BigUint) -> BigUint {
+        let mut sum = self.clone();
+        sum += other;
+        sum
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+    fn add(mut self, other: BigUint) -> BigUint {
+        self += &other;
+        self
+    }
+}
+
+impl Mul<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    /// Long multiplication, a limb of `self` times all of `other` at a time.
+    fn mul(self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::zero();
+        }
+        // every entry stays below `BASE` between rows, so a row can't overflow `u64`: (10^9)^2 + 2 * 10^9 < 2^64
+        let mut product = vec![0_u64; self.limbs.len() + other.limbs.len()];
+        for (i, &left) in self.limbs.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &right) in other.limbs.iter().enumerate() {
+                let entry = product[i + j] + left as u64 * right as u64 + carry;
+                (product[i + j], carry) = (entry % BASE as u64, entry / BASE as u64);
+            }
+            product[i + other.limbs.len()] = carry;
+        }
+        BigUint::trimmed(product.into_iter().map(|limb| limb as u32).collect())
+    }
+}
+
+impl Mul for BigUint {
+    type Output = BigUint;
+    fn mul(self, other: BigUint) -> BigUint {
+        &self * &other
+    }
+}
+
+impl MulAssign<&BigUint> for BigUint {
+    fn mul_assign(&mut self, other: &BigUint) {
+        *self = &*self * other;
+    }
+}
+
+impl MulAssign for BigUint {
+    fn mul_assign(&mut self, other: BigUint) {
+        *self = &*self * &other;
+    }
+}
+
+impl Sum for BigUint {
+    fn sum<T: Iterator<Item = BigUint>>(iter: T) -> Self {
+        iter.fold(BigUint::zero(), |sum, value| sum + value)
+    }
+}
+
+impl Product for BigUint {
+    fn product<T: Iterator<Item = BigUint>>(iter: T) -> Self {
+        iter.fold(BigUint::from(1_u8), |product, value| product * value)
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = match self.limbs.split_last() {
+            None => "0".to_string(),
+            Some((most, rest)) => rest.iter().rev().fold(most.to_string(), |digits, limb| format!("{}{:09}", digits, limb)),
+        };
+        // `{:>40}` and the like
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+/// What's wrong with a string which isn't a [`BigUint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseBigUintError {
+    Empty,
+    InvalidDigit(char),
+}
+
+impl fmt::Display for ParseBigUintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseBigUintError::Empty => write!(f, "cannot parse a number from an empty string"),
+            ParseBigUintError::InvalidDigit(c) => write!(f, "{:?} is not a decimal digit", c),
+        }
+    }
+}
+
+impl Error for ParseBigUintError {}
+
+impl FromStr for BigUint {
+    type Err = ParseBigUintError;
+
+    /// Decimal digits only, leading zeros are fine.
+    fn from_str(digits: &str) -> Result<Self, Self::Err> {
+        if digits.is_empty() {
+            return Err(ParseBigUintError::Empty);
+        }
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(ParseBigUintError::InvalidDigit(c));
+        }
+        // nine digits to a limb, starting from the least significant
+        let limbs = digits.as_bytes()
+            .rchunks(BASE_DIGITS)
+            .map(|chunk| chunk.iter().fold(0, |limb, digit| limb * 10 + (digit - b'0') as u32))
+            .collect();
+        Ok(BigUint::trimmed(limbs))
+    }
+}
+
+impl Spill for BigUint {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.limbs.write_to(out)
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        let limbs = Vec::<u32>::read_from(input)?;
+        if limbs.iter().any(|&limb| limb >= BASE) || limbs.last() == Some(&0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a big unsigned integer"));
+        }
+        Ok(BigUint { limbs })
+    }
+}
@@ -0,0 +1,240 @@
+use std::{fmt::Display, fs, io::{self, Write}, path::{Path, PathBuf}, str::FromStr, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+use crate::{clock::{Clock, RealClock}, ticker::{Ticker, TickerHandle}};
+
+const HEADER: &str = "lrn-rs ticker checkpoint v1";
+
+/// What a [`PersistentTicker`] remembers across restarts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint<P> {
+    /// Runs so far, over all restarts.
+    pub ticks: u64,
+    /// Wall clock time of the last run, an `Instant` means nothing to the next process.
+    pub last_run: Option<SystemTime>,
+    /// The ticker's own state, stored through its `Display` / `FromStr`.
+    pub payload: P,
+}
+
+/// ## A checkpoint on disk.
+/// A small text file, the payload goes last and verbatim so it may span lines:
+/// ```text
+/// lrn-rs ticker checkpoint v1
+/// ticks 42
+/// last_run 1700000000123456789
+/// payload
+/// <payload>
+/// ```
+/// [`CheckpointFile::save`] writes a temporary file next to it, syncs it and renames it over the old one,
+/// so a crash mid-write leaves either the old or the new checkpoint, never half of one.
+#[derive(Debug, Clone)]
+pub struct CheckpointFile {
+    path: PathBuf,
+}
+
+impl CheckpointFile {
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CheckpointFile { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `Ok(None)` if there is no checkpoint yet, `InvalidData` if the file isn't one.
+    pub fn load<P: FromStr>(&self) -> io::Result<Option<Checkpoint<P>>> {
+
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", self.path.display(), what));
+
+        let mut parts = text.splitn(5, '\n');
+        if parts.next() != Some(HEADER) {
+            return Err(invalid("not a ticker checkpoint"));
+        }
+        let ticks = parts.next()
+            .and_then(|line| line.strip_prefix("ticks "))
+            .and_then(|ticks| ticks.parse().ok())
+            .ok_or_else(|| invalid("bad `ticks` line"))?;
+        let last_run = match parts.next().and_then(|line| line.strip_prefix("last_run ")) {
+            Some("-") => None,
+            Some(nanos) => Some(UNIX_EPOCH + Duration::from_nanos(nanos.parse().map_err(|_| invalid("bad `last_run` line"))?)),
+            None => return Err(invalid("missing `last_run` line")),
+        };
+        if parts.next() != Some("payload") {
+            return Err(invalid("missing payload"));
+        }
+        let payload = parts.next().unwrap_or("").parse().map_err(|_| invalid("payload doesn't parse"))?;
+
+        Ok(Some(Checkpoint { ticks, last_run, payload }))
+    }
+
+    pub fn save<P: Display>(&self, checkpoint: &Checkpoint<P>) -> io::Result<()> {
+
+        let last_run = checkpoint.last_run
+            .map_or("-".to_string(), |at| at.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string());
+        let text = format!("{}\nticks {}\nlast_run {}\npayload\n{}", HEADER, checkpoint.ticks, last_run, checkpoint.payload);
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temporary, &self.path)?;
+
+        // make the rename itself durable, not every platform lets a directory be opened for that, so best effort
+        if let Some(directory) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            let _ = fs::File::open(directory).and_then(|directory| directory.sync_all());
+        }
+        Ok(())
+    }
+}
+
+
+/// ### What a restarted [`PersistentTicker`] does about the runs it missed while it was down.
+/// In every case the most recent missed run happens right away on start, like a cron job with `Persistent=true`.
+/// - `Skip`: only that one.
+/// - `All`: the older missed runs first, back to back.
+/// - `AtMost(n)`: like `All` but at most `n` of the older ones, for long outages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUp {
+    #[default]
+    Skip,
+    All,
+    AtMost(u64),
+}
+
+/// ## A [`Ticker`] whose state survives the process.
+/// The closure gets a `&mut P` instead of capturing an `Arc<Mutex<P>>` (as `ticker_async_with_mutex_and_stop` does with
+/// its counter), after every run the payload, tick count and time are checkpointed to a [`CheckpointFile`].
+/// Started again with the same file it picks up where the last process stopped:
+/// - still within the interval of the last run: the first tick waits until the interval is over,
+/// - one or more runs were missed: see [`CatchUp`].
+///
+/// A checkpoint which fails to save is reported on stderr and retried with the next tick.
+pub struct PersistentTicker<P, F> {
+    interval: Duration,
+    file: CheckpointFile,
+    initial: P,
+    func: F,
+    catch_up: CatchUp,
+    clock: Arc<dyn Clock>,
+}
+
+impl<P, F> PersistentTicker<P, F> where P: Display + FromStr + Send + 'static, F: FnMut(&mut P) + Send + 'static, {
+
+    /// `initial` is only used when there is no checkpoint yet.
+    pub fn new(file: CheckpointFile, interval: Duration, initial: P, func: F) -> Self {
+        PersistentTicker { interval, file, initial, func, catch_up: CatchUp::default(), clock: Arc::new(RealClock::default()) }
+    }
+
+    pub fn catch_up(mut self, catch_up: CatchUp) -> Self {
+        self.catch_up = catch_up;
+        self
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Loads the checkpoint and sets up the ticker, reading the file is the only thing that can fail.
+    fn ticker(self) -> io::Result<Ticker<impl FnMut() + Send + 'static>> {
+
+        let PersistentTicker { interval, file, initial, mut func, catch_up, clock } = self;
+        let mut checkpoint = file.load()?.unwrap_or(Checkpoint { ticks: 0, last_run: None, payload: initial });
+
+        // due runs since the last one, measured on the wall clock
+        let now = clock.system_now();
+        let since_last = checkpoint.last_run.map(|last_run| now.duration_since(last_run).unwrap_or_default());
+        let missed = since_last.map_or(0, |since| (since.as_nanos() / interval.as_nanos().max(1)) as u64);
+
+        let start_delay = match since_last {
+            Some(since) if missed == 0 => interval - since,
+            _ => Duration::ZERO,
+        };
+        let mut catch_up_runs = match catch_up {
+            CatchUp::Skip => 0,
+            CatchUp::All => missed.saturating_sub(1),
+            CatchUp::AtMost(limit) => missed.saturating_sub(1).min(limit),
+        };
+
+        let ticker_clock = Arc::clone(&clock);
+        let ticker = Ticker::new(interval, move || {
+            for _ in 0..std::mem::take(&mut catch_up_runs) + 1 {
+                func(&mut checkpoint.payload);
+                checkpoint.ticks += 1;
+                checkpoint.last_run = Some(clock.system_now());
+            }
+            if let Err(error) = file.save(&checkpoint) {
+                eprintln!("failed to save ticker checkpoint {}: {}", file.path().display(), error);
+            }
+        });
+
+        Ok(ticker.clock(ticker_clock).start_delay(start_delay))
+    }
+
+    /// Runs the ticker loop on a dedicated std thread, `Err` if an existing checkpoint can't be read.
+    pub fn start(self) -> io::Result<TickerHandle> {
+        Ok(self.ticker()?.start())
+    }
+
+    /// Runs the ticker loop as a tokio task, must be called from inside a tokio runtime.
+    pub fn start_async(self) -> io::Result<TickerHandle> {
+        Ok(self.ticker()?.start_async())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::ManualClock;
+    use super::*;
+
+    /// The counter of `ticker_async_with_mutex_and_stop` surviving three "restarts", simulated with manual clocks:
+    /// a quick one within the interval, one after 10 missed runs which are all caught up on,
+    /// and one after an outage far too long to catch up on completely.
+    #[test]
+    fn a_counter_survives_restarts() {
+        let file = CheckpointFile::new(std::env::temp_dir().join(format!("lrn-rs-ticker-{}.checkpoint", std::process::id())));
+        let _ = fs::remove_file(file.path());
+        let boot = SystemTime::now();
+
+        // (ms after the first boot the process starts, catch-up policy, seconds it runs, counter after it)
+        // first run: right away + 3, quick restart: 2 (the first one held back half a second),
+        // after 10 missed: 9 caught up + right away + 1, after an hour: 5 caught up + right away + 1
+        let runs = [
+            (0, CatchUp::Skip, 3, 4),
+            (3_500, CatchUp::Skip, 2, 4 + 2),
+            (16_000, CatchUp::All, 1, 4 + 2 + 11),
+            (3_600_000, CatchUp::AtMost(5), 1, 4 + 2 + 11 + 7),
+        ];
+
+        for (starts_at, catch_up, runs_for, counter) in runs {
+            let clock = ManualClock::starting_at(boot + Duration::from_millis(starts_at));
+            let handle = PersistentTicker::new(file.clone(), Duration::from_secs(1), 0u64, |counter| *counter += 1)
+                .catch_up(catch_up)
+                .clock(clock.clone())
+                .start()
+                .expect("checkpoint readable");
+
+            for _ in 0..runs_for {
+                clock.wait_for_idle(1);
+                clock.advance(Duration::from_secs(1));
+            }
+            clock.wait_for_idle(1);
+            handle.stop();
+            handle.join().unwrap();
+
+            assert_eq!(file.load::<u64>().unwrap().unwrap().payload, counter, "process started at +{}ms", starts_at);
+        }
+        let _ = fs::remove_file(file.path());
+    }
+}
@@ -0,0 +1,313 @@
+use std::{future::Future, pin::Pin, sync::{Arc, Condvar, Mutex}, time::{Duration, Instant, SystemTime}};
+use tokio::sync::Notify;
+
+/// ## Where the tickers get their time from.
+/// [`RealClock`] is the wall clock (`Instant::now`, `thread::sleep`, `tokio::time::sleep`),
+/// [`ManualClock`] only moves when [`ManualClock::advance`] is called, so tests can step through
+/// time by hand and see exactly which ticks fire without actually waiting for them.
+///
+/// Sleeping on a clock can be interrupted, this is how `pause()` / `stop()` reach a sleeping ticker:
+/// - a thread blocked in [`Clock::sleep_until`] re-checks its `interrupted` closure whenever [`Clock::interrupt`] is called,
+/// - a task just races [`Clock::sleep_until_async`] against its own `Notify` inside `tokio::select!`.
+///
+/// A `deadline` of `None` sleeps until interrupted (that's what a paused ticker does).
+pub trait Clock: Send + Sync + 'static {
+
+    fn now(&self) -> Instant;
+
+    /// Wall clock time matching [`Clock::now`], cron schedules need a calendar date which an `Instant` doesn't have.
+    fn system_now(&self) -> SystemTime;
+
+    /// Blocks the current thread until the clock reaches `deadline` or `interrupted()` returns true.
+    fn sleep_until(&self, deadline: Option<Instant>, interrupted: &dyn Fn() -> bool);
+
+    /// Resolves once the clock reached `deadline`, never resolves for `None`.
+    fn sleep_until_async(&self, deadline: Option<Instant>) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    /// Wakes up every thread blocked in [`Clock::sleep_until`] so they re-check their `interrupted` closure.
+    fn interrupt(&self);
+
+    fn sleep(&self, duration: Duration) {
+        self.sleep_until(Some(self.now() + duration), &|| false);
+    }
+
+    fn sleep_async(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.sleep_until_async(Some(self.now() + duration))
+    }
+}
+
+
+/// So a clock already shared behind an `Arc` can be handed on to a ticker or another component as is.
+impl Clock for Arc<dyn Clock> {
+
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        (**self).system_now()
+    }
+
+    fn sleep_until(&self, deadline: Option<Instant>, interrupted: &dyn Fn() -> bool) {
+        (**self).sleep_until(deadline, interrupted)
+    }
+
+    fn sleep_until_async(&self, deadline: Option<Instant>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        (**self).sleep_until_async(deadline)
+    }
+
+    fn interrupt(&self) {
+        (**self).interrupt()
+    }
+}
+
+
+/// The wall clock, the `Condvar` is only there so blocked threads can be interrupted.
+#[derive(Clone, Default)]
+pub struct RealClock {
+    inner: Arc<(Mutex<()>, Condvar)>,
+}
+
+impl Clock for RealClock {
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep_until(&self, deadline: Option<Instant>, interrupted: &dyn Fn() -> bool) {
+        let (lock, condvar) = &*self.inner;
+        let mut guard = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while !interrupted() {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return;
+                    }
+                    guard = condvar
+                        .wait_timeout(guard, deadline - now)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0;
+                }
+                None => guard = condvar.wait(guard).unwrap_or_else(|poisoned| poisoned.into_inner()),
+            }
+        }
+    }
+
+    fn sleep_until_async(&self, deadline: Option<Instant>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        match deadline {
+            Some(deadline) => Box::pin(tokio::time::sleep_until(deadline.into())),
+            None => Box::pin(std::future::pending()),
+        }
+    }
+
+    fn interrupt(&self) {
+        let (lock, condvar) = &*self.inner;
+        let _guard = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        condvar.notify_all();
+    }
+}
+
+
+/// ## A clock which only moves when told to.
+/// Cloning gives another handle to the same clock, so the test keeps one and hands the other to a ticker.
+///
+/// The clock also keeps track of everybody sleeping on it, [`ManualClock::wait_for_idle`] (and [`ManualClock::idle`]
+/// for async code) block until the sleepers are all waiting for a point in time which hasn't come yet,
+/// i.e. every tick which fell due after an `advance()` has run.
+///
+/// ```ignore
+/// let clock = ManualClock::new();
+/// let handle = Ticker::new(Duration::from_secs(1), || println!("tick")).clock(clock.clone()).start();
+/// clock.wait_for_idle(1);                 // first tick fired right away
+/// clock.advance(Duration::from_secs(3));  // three more are due now
+/// clock.wait_for_idle(1);
+/// assert_eq!(handle.ticks(), 4);
+/// ```
+#[derive(Clone)]
+pub struct ManualClock {
+    inner: Arc<ManualInner>,
+}
+
+struct ManualInner {
+    state: Mutex<ManualState>,
+    condvar: Condvar,
+    notify: Notify,
+}
+
+struct ManualState {
+    now: Instant,
+    // where `now` started, in `Instant` and in wall clock time
+    start: (Instant, SystemTime),
+    next_sleeper: u64,
+    // (sleeper id, deadline, interrupted) of everybody currently sleeping on the clock, an interrupted one isn't idle
+    // until it has re-checked why it was woken and gone back to sleep
+    sleepers: Vec<(u64, Option<Instant>, bool)>,
+}
+
+impl ManualState {
+    fn idle_sleepers(&self) -> usize {
+        self.sleepers.iter()
+            .filter(|(_, deadline, interrupted)| !interrupted && deadline.is_none_or(|deadline| deadline > self.now))
+            .count()
+    }
+
+    /// Marks sleeper `id` idle again, `true` if it had been interrupted.
+    fn back_to_sleep(&mut self, id: u64) -> bool {
+        self.sleepers.iter_mut()
+            .find(|(sleeper, _, _)| *sleeper == id)
+            .is_some_and(|(_, _, interrupted)| std::mem::replace(interrupted, false))
+    }
+}
+
+impl ManualInner {
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ManualState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn wake_all(&self) {
+        self.condvar.notify_all();
+        self.notify.notify_waiters();
+    }
+}
+
+/// Registers a sleeper for as long as it's alive, dropping it (also when a sleep future is cancelled) unregisters.
+struct Sleeper {
+    inner: Arc<ManualInner>,
+    id: u64,
+}
+
+impl Sleeper {
+    fn new(inner: &Arc<ManualInner>, deadline: Option<Instant>) -> Self {
+        let mut state = inner.lock();
+        let id = state.next_sleeper;
+        state.next_sleeper += 1;
+        state.sleepers.push((id, deadline, false));
+        drop(state);
+        inner.wake_all();
+        Sleeper { inner: Arc::clone(inner), id }
+    }
+}
+
+impl Drop for Sleeper {
+    fn drop(&mut self) {
+        self.inner.lock().sleepers.retain(|(id, _, _)| *id != self.id);
+        self.inner.wake_all();
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+
+    /// Starts at the current time, after that it never moves by itself.
+    pub fn new() -> Self {
+        Self::starting_at(SystemTime::now())
+    }
+
+    /// Same as [`ManualClock::new`] but the wall clock reads `system_time`, e.g. to test cron schedules.
+    pub fn starting_at(system_time: SystemTime) -> Self {
+        let now = Instant::now();
+        ManualClock {
+            inner: Arc::new(ManualInner {
+                state: Mutex::new(ManualState { now, start: (now, system_time), next_sleeper: 0, sleepers: Vec::new() }),
+                condvar: Condvar::new(),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Moves the clock forward and wakes up everybody whose deadline has been reached.
+    /// It doesn't wait for them to run, follow up with [`ManualClock::wait_for_idle`] for that.
+    pub fn advance(&self, duration: Duration) {
+        self.inner.lock().now += duration;
+        self.inner.wake_all();
+    }
+
+    /// Number of sleepers waiting for a deadline which hasn't been reached yet (or for no deadline at all).
+    pub fn idle_sleepers(&self) -> usize {
+        self.inner.lock().idle_sleepers()
+    }
+
+    /// Blocks until at least `sleepers` threads or tasks are sleeping on a deadline still in the future.
+    pub fn wait_for_idle(&self, sleepers: usize) {
+        let mut state = self.inner.lock();
+        while state.idle_sleepers() < sleepers {
+            state = self.inner.condvar.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Async version of [`ManualClock::wait_for_idle`].
+    pub async fn idle(&self, sleepers: usize) {
+        loop {
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.idle_sleepers() >= sleepers {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Clock for ManualClock {
+
+    fn now(&self) -> Instant {
+        self.inner.lock().now
+    }
+
+    fn system_now(&self) -> SystemTime {
+        let state = self.inner.lock();
+        state.start.1 + (state.now - state.start.0)
+    }
+
+    fn sleep_until(&self, deadline: Option<Instant>, interrupted: &dyn Fn() -> bool) {
+        let sleeper = Sleeper::new(&self.inner, deadline);
+        let mut state = self.inner.lock();
+        while !interrupted() && deadline.is_none_or(|deadline| state.now < deadline) {
+            if state.back_to_sleep(sleeper.id) {
+                self.inner.wake_all();
+            }
+            state = self.inner.condvar.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    fn sleep_until_async(&self, deadline: Option<Instant>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move {
+            let sleeper = Sleeper::new(&inner, deadline);
+            loop {
+                // enable before checking, so an `advance()` in between can't get lost
+                let notified = inner.notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                {
+                    let mut state = inner.lock();
+                    if deadline.is_some_and(|deadline| state.now >= deadline) {
+                        return;
+                    }
+                    if state.back_to_sleep(sleeper.id) {
+                        inner.wake_all();
+                    }
+                }
+                notified.await;
+            }
+        })
+    }
+
+    fn interrupt(&self) {
+        let mut state = self.inner.lock();
+        state.sleepers.iter_mut().for_each(|(_, _, interrupted)| *interrupted = true);
+        self.inner.wake_all();
+    }
+}
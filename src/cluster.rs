@@ -0,0 +1,587 @@
+use std::{collections::{BTreeMap, VecDeque}, env, fmt, hash::Hash, io::{self, Read, Write}, net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs}, process::{self, Child, Command}, sync::{atomic::{AtomicUsize, Ordering}, Arc, Condvar, Mutex, MutexGuard}, thread, time::{Duration, Instant}};
+use crate::{map_reduce::{word_count, words, JobError, JobReport, JobState, MapReduce, Phase, Segment, SegmentFailure, TaskError}, spill::Spill};
+
+/// The subcommand a worker process is started with, followed by the coordinator's address and the job's name.
+pub const WORKER_COMMAND: &str = "map-reduce-worker";
+
+// the first byte of every task a coordinator sends
+const DONE: u8 = 0;
+const MAP: u8 = 1;
+const REDUCE: u8 = 2;
+
+/// The biggest task or reply either side takes, a bigger length is taken for a broken (or hostile) peer.
+const MAX_FRAME: usize = 1 << 30;
+
+/// ## Runs the tasks of a [`MapReduce`] job in worker processes on the same machine.
+/// Workers connect over TCP (see [`Coordinator::spawn_worker`] and [`run_worker`]), each gets one task at a time:
+/// a map task with a segment's records, which sends back the segment's grouped (and combined) map output,
+/// or a reduce task with a reducer's partition, which sends back its output. The shuffle runs here in between.
+/// - A worker which closes its connection (it crashed) or doesn't answer within [`Coordinator::task_timeout`]
+///   loses its task to the next worker, the job report lists it as a [`TaskError::WorkerLost`] failure.
+///   A task lost [`Coordinator::max_attempts`] times, or no worker connected for a whole timeout, fails the job.
+/// - The job's functions run in the workers only, which build the same job under the same name,
+///   records, keys and values go over the socket in their [`Spill`] encoding.
+/// - Errors come back as their message, the error policy applies per task (`skip_bad_records` counts per segment),
+///   and the job's [`MapReduce::spill_to`] doesn't apply, the coordinator keeps the partitions in memory.
+#[derive(Debug)]
+pub struct Coordinator {
+    listener: TcpListener,
+    task_timeout: Duration,
+    max_attempts: u32,
+}
+
+impl Coordinator {
+
+    /// `"127.0.0.1:0"` for any free port, see [`Coordinator::local_addr`].
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        // so it can stop accepting once the job is done
+        listener.set_nonblocking(true)?;
+        Ok(Coordinator { listener, task_timeout: Duration::from_secs(10), max_attempts: 3 })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// How long a worker may take for a task (10 seconds unless told otherwise), it has to be more than the slowest takes.
+    pub fn task_timeout(mut self, timeout: Duration) -> Self {
+        self.task_timeout = timeout;
+        self
+    }
+
+    /// How many workers may lose a task (3 unless told otherwise), a record which crashes every worker fails the job after that.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Starts this executable again as a worker for the job `name`, with `vars` added to its environment,
+    /// its `main` has to hand [`WORKER_COMMAND`] and the two arguments after it to [`worker_main`].
+    pub fn spawn_worker(&self, name: &str, vars: &[(&str, &str)]) -> io::Result<Child> {
+        Command::new(env::current_exe()?)
+            .args([WORKER_COMMAND, &self.local_addr()?.to_string(), name])
+            .envs(vars.iter().copied())
+            .spawn()
+    }
+
+    /// Runs `job` on the workers which connect for `name`, until it's done or failed.
+    pub fn run<I, K, V, E>(&self, name: &str, job: &MapReduce<I, K, V, E>, inputs: impl IntoIterator<Item = I>) -> Result<JobReport<K, V, String>, JobError<String>>
+    where I: Spill + Send, K: Spill + Ord + Hash + Send, V: Spill + Send, E: fmt::Display + Send,
+    {
+        let farm = Farm::new(self.max_attempts);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                while !farm.lock().finished {
+                    match self.listener.accept() {
+                        Ok((stream, _)) => {
+                            let farm = &farm;
+                            scope.spawn(move || serve(farm, stream, name, self.task_timeout));
+                        }
+                        Err(error) if error.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(10)),
+                        Err(_) => {}
+                    }
+                }
+            });
+            let result = self.run_job(&farm, job, inputs);
+            farm.finish();
+            result
+        })
+    }
+
+    fn run_job<I, K, V, E>(&self, farm: &Farm, job: &MapReduce<I, K, V, E>, inputs: impl IntoIterator<Item = I>) -> Result<JobReport<K, V, String>, JobError<String>>
+    where I: Spill + Send, K: Spill + Ord + Hash + Send, V: Spill + Send, E: fmt::Display + Send,
+    {
+        let mut failures = Vec::new();
+
+        let (mut inputs, mut next_segment) = (inputs.into_iter(), 0);
+        let mut partitions = job.partitions();
+        loop {
+            let segments = job.segments(&mut inputs, &mut next_segment);
+            if segments.is_empty() {
+                break;
+            }
+            let indices = segments.iter().map(|segment| segment.index).collect::<Vec<_>>();
+            let tasks = segments.into_iter().map(|Segment { index, offset, records }| task(MAP, &((index, offset), records))).collect();
+
+            let mut grouped = Vec::with_capacity(indices.len());
+            let mut failed = false;
+            for (task, reply) in self.batch(farm, Phase::Map, &indices, tasks, &mut failures)?.into_iter().enumerate() {
+                let (output, task_failures, task_failed) = decode::<(BTreeMap<K, Vec<V>>, Vec<SegmentFailure<String>>, bool)>(reply)
+                    .map_err(|error| bad_reply(&mut failures, Phase::Map, indices[task], error))?;
+                failures.extend(task_failures);
+                failed |= task_failed;
+                grouped.push(output);
+            }
+            if failed {
+                return Err(JobError { failures });
+            }
+            job.shuffle(&mut partitions, grouped).map_err(|failure| {
+                failures.push(displayed(failure));
+                JobError { failures: std::mem::take(&mut failures) }
+            })?;
+        }
+
+        let reducers = (0..partitions.len()).collect::<Vec<_>>();
+        let tasks = partitions.into_iter().enumerate().map(|partition| task(REDUCE, &partition)).collect();
+        let mut output = BTreeMap::new();
+        let mut failed = false;
+        for (reducer, reply) in self.batch(farm, Phase::Reduce, &reducers, tasks, &mut failures)?.into_iter().enumerate() {
+            match decode::<Result<BTreeMap<K, V>, SegmentFailure<String>>>(reply).map_err(|error| bad_reply(&mut failures, Phase::Reduce, reducer, error))? {
+                Ok(partition) => output.extend(partition),
+                Err(failure) => {
+                    failures.push(failure);
+                    failed = true;
+                }
+            }
+        }
+        match failed {
+            true => Err(JobError { failures }),
+            false => Ok(JobReport { output, failures, spilled_runs: 0 }),
+        }
+    }
+
+    /// Hands out a batch of tasks and waits for all of them, the lost ones go to `failures`.
+    fn batch(&self, farm: &Farm, phase: Phase, indices: &[usize], tasks: Vec<Vec<u8>>, failures: &mut Vec<SegmentFailure<String>>) -> Result<Vec<Vec<u8>>, JobError<String>> {
+        let (replies, lost) = farm.run(tasks, self.task_timeout);
+        failures.extend(lost.into_iter().map(|Lost { task, attempt, reason }| SegmentFailure {
+            phase,
+            index: indices[task],
+            record: None,
+            attempt,
+            error: TaskError::WorkerLost(reason),
+        }));
+        replies.ok_or_else(|| JobError { failures: std::mem::take(failures) })
+    }
+}
+
+/// A reply which doesn't decode fails the job, the worker runs another build or job.
+fn bad_reply(failures: &mut Vec<SegmentFailure<String>>, phase: Phase, index: usize, error: io::Error) -> JobError<String> {
+    failures.push(SegmentFailure { phase, index, record: None, attempt: 1, error: TaskError::WorkerLost(format!("bad reply: {}", error)) });
+    JobError { failures: std::mem::take(failures) }
+}
+
+
+/// A task some worker lost, on its `attempt`th time out.
+struct Lost {
+    task: usize,
+    attempt: u32,
+    reason: String,
+}
+
+/// ## The tasks of the current batch, shared by the connections to the workers.
+/// A connection takes the next pending task, sends it and waits for the reply, a task it loses goes back
+/// to the front of the queue. The coordinator waits for the batch's last reply, or gives up.
+struct Farm {
+    state: Mutex<FarmState>,
+    changed: Condvar,
+}
+
+struct FarmState {
+    tasks: Vec<Arc<Vec<u8>>>,
+    pending: VecDeque<usize>,
+    replies: Vec<Option<Vec<u8>>>,
+    attempts: Vec<u32>,
+    lost: Vec<Lost>,
+    max_attempts: u32,
+    /// Set when a task was lost too often.
+    given_up: bool,
+    workers: usize,
+    finished: bool,
+}
+
+impl Farm {
+
+    fn new(max_attempts: u32) -> Self {
+        let state = FarmState {
+            tasks: Vec::new(),
+            pending: VecDeque::new(),
+            replies: Vec::new(),
+            attempts: Vec::new(),
+            lost: Vec::new(),
+            max_attempts,
+            given_up: false,
+            workers: 0,
+            finished: false,
+        };
+        Farm { state: Mutex::new(state), changed: Condvar::new() }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FarmState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Every task's reply, `None` if the batch was given up on, and the tasks lost on the way.
+    fn run(&self, tasks: Vec<Vec<u8>>, timeout: Duration) -> (Option<Vec<Vec<u8>>>, Vec<Lost>) {
+        let mut state = self.lock();
+        state.pending = (0..tasks.len()).collect();
+        state.replies = tasks.iter().map(|_| None).collect();
+        state.attempts = vec![0; tasks.len()];
+        state.tasks = tasks.into_iter().map(Arc::new).collect();
+        self.changed.notify_all();
+
+        let mut idle_since = Instant::now();
+        while !state.given_up && state.replies.iter().any(Option::is_none) {
+            let (next, _) = self.changed.wait_timeout(state, timeout).unwrap_or_else(|poisoned| poisoned.into_inner());
+            state = next;
+            if state.workers > 0 {
+                idle_since = Instant::now();
+            } else if idle_since.elapsed() >= timeout {
+                let task = state.replies.iter().position(Option::is_none).expect("a task is left");
+                let attempt = state.attempts[task].max(1);
+                state.lost.push(Lost { task, attempt, reason: format!("no worker connected for {:?}", timeout) });
+                state.given_up = true;
+            }
+        }
+
+        let lost = std::mem::take(&mut state.lost);
+        state.pending.clear();
+        match state.given_up {
+            true => (None, lost),
+            false => (Some(state.replies.iter_mut().map(|reply| reply.take().expect("every task has its reply")).collect()), lost),
+        }
+    }
+
+    /// Waits for the next task, `None` once the job is done.
+    fn next(&self) -> Option<(usize, Arc<Vec<u8>>)> {
+        let mut state = self.lock();
+        loop {
+            if state.finished {
+                return None;
+            }
+            if let Some(task) = state.pending.pop_front() {
+                state.attempts[task] += 1;
+                return Some((task, Arc::clone(&state.tasks[task])));
+            }
+            state = self.changed.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    fn done(&self, task: usize, reply: Vec<u8>) {
+        let mut state = self.lock();
+        if let Some(slot) = state.replies.get_mut(task) {
+            *slot = Some(reply);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Back to the front of the queue for the next worker, unless it was lost too often.
+    fn lose(&self, task: usize, reason: String) {
+        let mut state = self.lock();
+        let attempt = state.attempts[task];
+        state.lost.push(Lost { task, attempt, reason });
+        match attempt < state.max_attempts {
+            true => state.pending.push_front(task),
+            false => state.given_up = true,
+        }
+        self.changed.notify_all();
+    }
+
+    fn connected(&self, change: isize) {
+        let mut state = self.lock();
+        state.workers = state.workers.wrapping_add_signed(change);
+        self.changed.notify_all();
+    }
+
+    fn finish(&self) {
+        self.lock().finished = true;
+        self.changed.notify_all();
+    }
+}
+
+/// One worker's connection: checks it's there for the job, then hands it tasks until the job is done or it's lost.
+fn serve(farm: &Farm, mut stream: TcpStream, name: &str, timeout: Duration) {
+    let ready = stream.set_nonblocking(false).and_then(|()| stream.set_read_timeout(Some(timeout))).and_then(|()| stream.set_nodelay(true));
+    // whoever connected hasn't shown it's a worker yet, it gets no more than the name's length
+    if ready.is_err() || read_frame(&mut stream, name.len()).ok().as_deref() != Some(name.as_bytes()) {
+        return;
+    }
+
+    farm.connected(1);
+    while let Some((task, payload)) = farm.next() {
+        match write_frame(&mut stream, &payload).and_then(|()| read_frame(&mut stream, MAX_FRAME)) {
+            Ok(reply) => farm.done(task, reply),
+            Err(error) => {
+                let reason = match error.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => format!("no answer within {:?}", timeout),
+                    io::ErrorKind::UnexpectedEof => "connection closed".to_string(),
+                    _ => format!("connection lost: {}", error),
+                };
+                farm.lose(task, reason);
+                farm.connected(-1);
+                return;
+            }
+        }
+    }
+    let _ = write_frame(&mut stream, &[DONE]);
+    farm.connected(-1);
+}
+
+
+/// ## The worker side: connects to the coordinator at `address` for the job `name` and runs its tasks until it's done.
+/// `job` has to be the same job the coordinator runs under that name, built the same way.
+pub fn run_worker<I, K, V, E>(name: &str, job: &MapReduce<I, K, V, E>, address: impl ToSocketAddrs) -> io::Result<()>
+where I: Spill + Send, K: Spill + Ord + Hash + Send, V: Spill + Send, E: fmt::Display + Send,
+{
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    write_frame(&mut stream, name.as_bytes())?;
+
+    loop {
+        let task = read_frame(&mut stream, MAX_FRAME)?;
+        let Some((&kind, mut payload)) = task.split_first() else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty task"));
+        };
+        let reply = match kind {
+            DONE => return Ok(()),
+            MAP => {
+                let ((index, offset), records) = <((usize, usize), Vec<I>)>::read_from(&mut payload)?;
+                let output = job.map_segment(Segment { index, offset, records }, &JobState::default());
+                encode(&(output.grouped, output.failures.into_iter().map(displayed).collect::<Vec<_>>(), output.failed))
+            }
+            REDUCE => {
+                let (reducer, partition) = <(usize, BTreeMap<K, Vec<V>>)>::read_from(&mut payload)?;
+                encode(&job.reduce_partition(reducer, partition, Vec::new(), &JobState::default()).map_err(displayed))
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown task {}", kind))),
+        };
+        write_frame(&mut stream, &reply)?;
+    }
+}
+
+fn displayed<E: fmt::Display>(failure: SegmentFailure<E>) -> SegmentFailure<String> {
+    let error = match failure.error {
+        TaskError::Failed(error) => TaskError::Failed(error.to_string()),
+        TaskError::Panicked(message) => TaskError::Panicked(message),
+        TaskError::Spill(error) => TaskError::Spill(error),
+        TaskError::WorkerLost(reason) => TaskError::WorkerLost(reason),
+        TaskError::Cancelled => TaskError::Cancelled,
+    };
+    SegmentFailure { phase: failure.phase, index: failure.index, record: failure.record, attempt: failure.attempt, error }
+}
+
+/// A task starts with its kind, a reply doesn't, the coordinator knows what it asked for.
+fn task(kind: u8, message: &impl Spill) -> Vec<u8> {
+    let mut bytes = vec![kind];
+    bytes.extend(encode(message));
+    bytes
+}
+
+fn encode(message: &impl Spill) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    message.write_to(&mut bytes).expect("writing to a Vec doesn't fail");
+    bytes
+}
+
+fn decode<T: Spill>(bytes: Vec<u8>) -> io::Result<T> {
+    T::read_from(&mut bytes.as_slice())
+}
+
+fn write_frame(stream: &mut TcpStream, bytes: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(size_of::<usize>() + bytes.len());
+    bytes.len().write_to(&mut frame)?;
+    frame.extend_from_slice(bytes);
+    stream.write_all(&frame)
+}
+
+/// A frame of at most `max` bytes, read as it arrives rather than allocated up front from the length.
+fn read_frame(stream: &mut TcpStream, max: usize) -> io::Result<Vec<u8>> {
+    let length = usize::read_from(stream)?;
+    if length > max {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes, more than {}", length, max)));
+    }
+    let mut bytes = Vec::new();
+    Read::take(&mut *stream, length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+impl Spill for Phase {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        (*self == Phase::Map).write_to(out)
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        Ok(if bool::read_from(input)? { Phase::Map } else { Phase::Reduce })
+    }
+}
+
+/// A spill error only keeps its message on the way.
+impl Spill for TaskError<String> {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        let (kind, message) = match self {
+            TaskError::Failed(error) => (0_u8, error.clone()),
+            TaskError::Panicked(message) => (1, message.clone()),
+            TaskError::Spill(error) => (2, error.to_string()),
+            TaskError::WorkerLost(reason) => (3, reason.clone()),
+            TaskError::Cancelled => (4, String::new()),
+        };
+        (kind, message).write_to(out)
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        match <(u8, String)>::read_from(input)? {
+            (0, error) => Ok(TaskError::Failed(error)),
+            (1, message) => Ok(TaskError::Panicked(message)),
+            (2, error) => Ok(TaskError::Spill(io::Error::other(error))),
+            (3, reason) => Ok(TaskError::WorkerLost(reason)),
+            (4, _) => Ok(TaskError::Cancelled),
+            (kind, _) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown task error {}", kind))),
+        }
+    }
+}
+
+impl Spill for SegmentFailure<String> {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        (self.phase, self.index, self.record).write_to(out)?;
+        self.attempt.write_to(out)?;
+        self.error.write_to(out)
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        let (phase, index, record) = Spill::read_from(input)?;
+        Ok(SegmentFailure { phase, index, record, attempt: u32::read_from(input)?, error: TaskError::read_from(input)? })
+    }
+}
+
+
+/// ## Runs a worker for the job `name` of the coordinator at `address`, then exits.
+/// What `lrn-rs map-reduce-worker <address> <name>` does, see [`Coordinator::spawn_worker`].
+/// Knows the jobs of [`map_reduce_cluster_main`] by name.
+pub fn worker_main(address: &str, name: &str) {
+    let result = match name {
+        "word_count" => run_worker(name, &word_count::<String>(), address),
+        "flaky_word_count" => run_worker(name, &flaky_word_count(), address),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no job named {:?}", name))),
+    };
+    if let Err(error) = result {
+        eprintln!("worker {} for {:?} failed: {}", process::id(), name, error);
+        process::exit(1);
+    }
+    process::exit(0);
+}
+
+/// ## Coordinates the job `name` for workers started elsewhere, then exits.
+/// Prints the address they connect to on the first line, runs the job on the lines of stdin and prints its output,
+/// a key and its value to a line, then `lost: <failure>` for every task a worker lost on the way.
+/// Knows the same jobs as [`worker_main`].
+pub fn coordinator_main(name: &str) {
+    let result = Coordinator::bind("127.0.0.1:0").and_then(|coordinator| {
+        println!("{}", coordinator.local_addr()?);
+        let lines = io::stdin().lines().collect::<io::Result<Vec<_>>>()?;
+        let job = match name {
+            "word_count" => word_count(),
+            "flaky_word_count" => flaky_word_count(),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no job named {:?}", name))),
+        };
+        coordinator.run(name, &job, lines).map_err(io::Error::other)
+    });
+    match result {
+        Ok(report) => {
+            print_report(&report);
+            process::exit(0);
+        }
+        Err(error) => {
+            eprintln!("coordinator for {:?} failed: {}", name, error);
+            process::exit(1);
+        }
+    }
+}
+
+/// A word count's output, a word and its count to a line, then `lost: <failure>` for every lost task.
+fn print_report(report: &JobReport<String, u64, String>) {
+    report.output.iter().for_each(|(word, count)| println!("{} {}", word, count));
+    report.failures.iter().for_each(|failure| println!("lost: {}", failure));
+}
+
+/// Set to make a worker of [`flaky_word_count`] exit at the record with that number (counted from 0 in the worker).
+const CRASH_AT: &str = "LRN_RS_CRASH_AT";
+/// Same, but the worker says so on stderr and stops answering.
+const STALL_AT: &str = "LRN_RS_STALL_AT";
+
+/// Word count whose worker crashes or hangs if told so in its environment.
+fn flaky_word_count() -> MapReduce<String, String, u64> {
+    let at = |var| env::var(var).ok().and_then(|record: String| record.parse::<usize>().ok());
+    let (crash_at, stall_at) = (at(CRASH_AT), at(STALL_AT));
+    let records = AtomicUsize::new(0);
+    MapReduce::fold(
+        move |line: String, out| {
+            let record = records.fetch_add(1, Ordering::SeqCst);
+            if Some(record) == crash_at {
+                process::exit(101);
+            }
+            if Some(record) == stall_at {
+                eprintln!("worker {} stalls at record {}", process::id(), record);
+                loop {
+                    thread::sleep(Duration::from_secs(60));
+                }
+            }
+            words(&line).for_each(|word| out.emit(word, 1));
+        },
+        |a, b| a + b,
+    )
+    .segment_size(50)
+}
+
+/// Word count in three worker processes, one of which crashes at its first record and one hangs there,
+/// their tasks go to the third. `lrn-rs map-reduce-cluster` runs it and prints what [`coordinator_main`] prints,
+/// `tests/cluster.rs` checks that it's the same output as in one process.
+pub fn map_reduce_cluster_main() -> io::Result<()> {
+
+    let lines = (0..2_000).map(|line| format!("line {} of the map phase, segment {}", line % 7, line / 50)).collect::<Vec<_>>();
+    let coordinator = Coordinator::bind("127.0.0.1:0")?.task_timeout(Duration::from_secs(1)).max_attempts(3);
+    let mut workers = vec![
+        coordinator.spawn_worker("flaky_word_count", &[])?,
+        coordinator.spawn_worker("flaky_word_count", &[(CRASH_AT, "0")])?,
+        coordinator.spawn_worker("flaky_word_count", &[(STALL_AT, "0")])?,
+    ];
+
+    let started = Instant::now();
+    let report = coordinator.run("flaky_word_count", &flaky_word_count(), lines);
+    // the one which hangs is still there
+    for worker in &mut workers {
+        worker.kill()?;
+        worker.wait()?;
+    }
+    let report = report.map_err(io::Error::other)?;
+    eprintln!("{} words on {} workers in {:.2?}", report.output.len(), workers.len(), started.elapsed());
+    print_report(&report);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Both ends of a fresh connection.
+    fn connected() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        (client, listener.accept().unwrap().0)
+    }
+
+    #[test]
+    fn frames_come_through_whole() {
+        let (mut client, mut server) = connected();
+        write_frame(&mut client, b"a task").unwrap();
+        write_frame(&mut client, b"").unwrap();
+        assert_eq!(read_frame(&mut server, 6).unwrap(), b"a task");
+        assert_eq!(read_frame(&mut server, 6).unwrap(), b"");
+    }
+
+    #[test]
+    fn an_oversized_frame_is_refused_before_it_arrives() {
+        let (mut client, mut server) = connected();
+        // nothing but the length, reading any further would hang
+        usize::MAX.write_to(&mut client).unwrap();
+        assert_eq!(read_frame(&mut server, MAX_FRAME).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn a_connection_closed_mid_frame_is_an_early_eof() {
+        let (mut client, mut server) = connected();
+        100_usize.write_to(&mut client).unwrap();
+        client.write_all(b"not a hundred bytes").unwrap();
+        drop(client);
+        assert_eq!(read_frame(&mut server, MAX_FRAME).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,139 @@
+use std::{num::NonZeroUsize, sync::Mutex, thread};
+
+/// ## Where the phases of a [`MapReduce`](crate::map_reduce::MapReduce) run.
+/// An executor gets a batch of independent tasks and hands back their results in the same order,
+/// how many run at once and on which threads is up to it.
+pub trait Executor {
+
+    fn execute<T, R, F>(&self, tasks: Vec<T>, task: F) -> Vec<R> where T: Send, R: Send, F: Fn(T) -> R + Sync;
+
+    /// Like [`Executor::execute`] but the executor may cut big tasks into pieces, the results of a task's pieces
+    /// are put back together with `join` (left piece first). Executors which don't split just run the tasks whole.
+    fn execute_splittable<T, R, F, J>(&self, tasks: Vec<T>, task: F, join: J) -> Vec<R>
+    where T: Splittable + Send, R: Send, F: Fn(T) -> R + Sync, J: Fn(R, R) -> R + Sync,
+    {
+        let _ = join;
+        self.execute(tasks, task)
+    }
+}
+
+/// A task which can be cut in two, e.g. a map segment into its first and second half of records.
+pub trait Splittable: Sized {
+
+    /// How much work is in the task, in whatever unit `split` halves.
+    fn size(&self) -> usize;
+
+    /// The first and the second half, in order.
+    fn split(self) -> (Self, Self);
+}
+
+impl<T> Splittable for Vec<T> {
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    fn split(mut self) -> (Self, Self) {
+        let second = self.split_off(self.len() / 2);
+        (self, second)
+    }
+}
+
+/// Everything on the calling thread, one task after the other, what `map_reduce_sync` always did.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncExecutor;
+
+impl Executor for SyncExecutor {
+    fn execute<T, R, F>(&self, tasks: Vec<T>, task: F) -> Vec<R> where T: Send, R: Send, F: Fn(T) -> R + Sync {
+        tasks.into_iter().map(task).collect()
+    }
+}
+
+/// ## `threads` scoped threads, started per batch, each taking the next task off a shared queue until it's empty.
+/// A thread which got quick tasks simply takes more, so uneven tasks balance out on their own.
+/// A task which panics makes `execute` panic once the other threads are done.
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadExecutor {
+    threads: usize,
+}
+
+impl Default for ThreadExecutor {
+    /// One thread per core.
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+impl ThreadExecutor {
+    pub fn new(threads: usize) -> Self {
+        ThreadExecutor { threads: threads.max(1) }
+    }
+}
+
+impl Executor for ThreadExecutor {
+    fn execute<T, R, F>(&self, tasks: Vec<T>, task: F) -> Vec<R> where T: Send, R: Send, F: Fn(T) -> R + Sync {
+
+        let count = tasks.len();
+        let queue = Mutex::new(tasks.into_iter().enumerate());
+        let (queue, task) = (&queue, &task);
+
+        let mut results = thread::scope(|scope| {
+            let workers = (0..self.threads.min(count))
+                .map(|_| scope.spawn(move || {
+                    let mut done = Vec::new();
+                    // the lock is only held to take the next task, not while running it
+                    while let Some((index, next)) = { let next = queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).next(); next } {
+                        done.push((index, task(next)));
+                    }
+                    done
+                }))
+                .collect::<Vec<_>>();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap_or_else(|payload| std::panic::resume_unwind(payload))).collect::<Vec<_>>()
+        });
+
+        results.sort_unstable_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+
+/// ## `threads` scoped threads, each getting a fixed, contiguous share of the tasks up front.
+/// The least overhead there is for tasks which all take about as long, but a thread which got the slow ones
+/// finishes last while the others have long been idle.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticSplitExecutor {
+    threads: usize,
+}
+
+impl Default for StaticSplitExecutor {
+    /// One thread per core.
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+impl StaticSplitExecutor {
+    pub fn new(threads: usize) -> Self {
+        StaticSplitExecutor { threads: threads.max(1) }
+    }
+}
+
+impl Executor for StaticSplitExecutor {
+    fn execute<T, R, F>(&self, mut tasks: Vec<T>, task: F) -> Vec<R> where T: Send, R: Send, F: Fn(T) -> R + Sync {
+
+        let share = tasks.len().div_ceil(self.threads).max(1);
+        let mut shares = Vec::new();
+        while !tasks.is_empty() {
+            let rest = tasks.split_off(share.min(tasks.len()));
+            shares.push(std::mem::replace(&mut tasks, rest));
+        }
+        let task = &task;
+
+        thread::scope(|scope| {
+            let workers = shares.into_iter()
+                .map(|share| scope.spawn(move || share.into_iter().map(task).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap_or_else(|payload| std::panic::resume_unwind(payload))).collect()
+        })
+    }
+}
@@ -0,0 +1,194 @@
+use std::{collections::VecDeque, fs, io::{self, BufRead, BufReader}, path::{Path, PathBuf}};
+
+/// ## Cuts the bytes of an input into records.
+/// - [`Lines`]: a record per line,
+/// - [`ByteRanges`]: records of about the same number of bytes, always ending on a record boundary,
+/// - any `Fn(&mut dyn BufRead) -> io::Result<Option<String>>` for everything else.
+pub trait Splitter: Send + Sync {
+    /// The next record, `None` once the reader is exhausted.
+    fn next_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<String>>;
+}
+
+impl<F> Splitter for F where F: Fn(&mut dyn BufRead) -> io::Result<Option<String>> + Send + Sync, {
+    fn next_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+        self(reader)
+    }
+}
+
+/// A record per line, without the line ending.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Lines;
+
+impl Splitter for Lines {
+    fn next_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+/// ## Records of at least `size` bytes, extended to the next `delimiter` (a newline unless told otherwise).
+/// Like the input splits of Hadoop, a range never ends in the middle of a line, so a map function over a range
+/// sees whole lines only. Fewer, bigger records than [`Lines`] mean less overhead per record.
+#[derive(Debug, Clone, Copy)]
+pub struct ByteRanges {
+    size: usize,
+    delimiter: u8,
+}
+
+impl ByteRanges {
+
+    pub fn new(size: usize) -> Self {
+        ByteRanges { size: size.max(1), delimiter: b'\n' }
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+impl Splitter for ByteRanges {
+    fn next_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+        let mut range = Vec::with_capacity(self.size);
+        while range.len() < self.size {
+            if reader.read_until(self.delimiter, &mut range)? == 0 {
+                break;
+            }
+        }
+        if range.is_empty() {
+            return Ok(None);
+        }
+        String::from_utf8(range).map(Some).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+
+enum Source {
+    File(PathBuf),
+    Stdin,
+    Reader(Box<dyn BufRead + Send>),
+}
+
+/// ## Records read from files, directories or stdin, one at a time.
+/// An iterator over the records of all its sources in order, each source split by itself (a record never spans
+/// two files). A file is only opened once the one before it is done and only read as far as the records taken,
+/// so a [`MapReduce`](crate::map_reduce::MapReduce) reading ahead a bounded number of segments
+/// (see `MapReduce::read_ahead`) never holds more of the input than that.
+/// ```ignore
+/// let counts = word_count().run_input_on(&ThreadPool::default(), Input::dir("logs")?.split(ByteRanges::new(1 << 20)))?;
+/// ```
+pub struct Input {
+    sources: VecDeque<Source>,
+    splitter: Box<dyn Splitter>,
+    current: Option<Box<dyn BufRead + Send>>,
+}
+
+impl Input {
+
+    fn with(source: Source) -> Self {
+        Input { sources: VecDeque::from([source]), splitter: Box::new(Lines), current: None }
+    }
+
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::with(Source::File(path.into()))
+    }
+
+    /// Every file below `path`, subdirectories included, in the order of their paths.
+    pub fn dir(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut files = Vec::new();
+        files_below(path.as_ref(), &mut files)?;
+        files.sort();
+        Ok(Input { sources: files.into_iter().map(Source::File).collect(), splitter: Box::new(Lines), current: None })
+    }
+
+    pub fn stdin() -> Self {
+        Self::with(Source::Stdin)
+    }
+
+    pub fn reader(reader: impl BufRead + Send + 'static) -> Self {
+        Self::with(Source::Reader(Box::new(reader)))
+    }
+
+    /// Reads `other`'s sources after this one's.
+    pub fn chain(mut self, other: Input) -> Self {
+        self.sources.extend(other.sources);
+        self
+    }
+
+    /// The size of the files it hasn't started reading, `None` if stdin or a reader is among the sources.
+    /// For the progress of a job, see `MapReduce::expected_bytes`.
+    pub fn total_bytes(&self) -> io::Result<Option<u64>> {
+        let mut total = 0;
+        for source in &self.sources {
+            match source {
+                Source::File(path) => total += fs::metadata(path)?.len(),
+                Source::Stdin | Source::Reader(_) => return Ok(None),
+            }
+        }
+        Ok(Some(total))
+    }
+
+    /// [`Lines`] unless told otherwise.
+    pub fn split(mut self, splitter: impl Splitter + 'static) -> Self {
+        self.splitter = Box::new(splitter);
+        self
+    }
+}
+
+fn files_below(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            files_below(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+impl Iterator for Input {
+
+    /// An error ends the input, it's the last item.
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let reader = match &mut self.current {
+                Some(reader) => reader,
+                None => {
+                    let reader: Box<dyn BufRead + Send> = match self.sources.pop_front()? {
+                        Source::File(path) => match fs::File::open(&path) {
+                            Ok(file) => Box::new(BufReader::new(file)),
+                            Err(error) => {
+                                self.sources.clear();
+                                return Some(Err(io::Error::new(error.kind(), format!("{}: {}", path.display(), error))));
+                            }
+                        },
+                        Source::Stdin => Box::new(BufReader::new(io::stdin())),
+                        Source::Reader(reader) => reader,
+                    };
+                    self.current.insert(reader)
+                }
+            };
+            match self.splitter.next_record(reader) {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => self.current = None,
+                Err(error) => {
+                    self.current = None;
+                    self.sources.clear();
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
@@ -12,6 +12,28 @@ mod leetcode;
 mod my_mod;
 mod atomics;
 mod ticker;
+mod clock;
+mod schedule;
+mod tick_source;
+mod shutdown;
+mod metrics;
+mod tick_stream;
+mod timer_wheel;
+mod rate_limit;
+mod retry;
+mod watchdog;
+mod checkpoint;
+mod executor;
+mod thread_pool;
+mod work_stealing;
+mod shuffle;
+mod input;
+mod spill;
+mod cluster;
+mod progress;
+mod reorder;
+mod big_uint;
+mod accumulate;
 
 use std::fmt::{Debug, Display};
 use r#async::__exmaple_channels;
@@ -409,10 +431,21 @@ fn debug<'a>(a: &'a str, b: &'a str) {
 
 #[tokio::main]
 async fn main() {
-    // ticker::ticker_main();
-    // ticker::ticker_mpsc_main();
-    // ticker::ticker_mpsc_external_main();
-    ticker::ticker_async_with_mutex_and_stop().await;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        // a map-reduce worker process, see `cluster::Coordinator::spawn_worker`
+        [cluster::WORKER_COMMAND, address, job] => cluster::worker_main(address, job),
+        // the coordinator for workers started elsewhere, see `tests/cluster.rs`
+        ["map-reduce", job] => cluster::coordinator_main(job),
+        ["map-reduce-cluster"] => if let Err(error) = cluster::map_reduce_cluster_main() {
+            eprintln!("the cluster demo failed: {}", error);
+            std::process::exit(1);
+        },
+        // ticker::ticker_main();
+        // ticker::ticker_mpsc_main();
+        // ticker::ticker_mpsc_external_main();
+        _ => ticker::ticker_async_with_mutex_and_stop().await,
+    }
 }
 
 fn main3() {
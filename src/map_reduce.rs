@@ -1,4 +1,5 @@
-use std::{thread, time::Duration};
+use std::{collections::{BTreeMap, BTreeSet}, convert::Infallible, env, error::Error, fmt, fs, hash::Hash, io, num::NonZeroUsize, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc}, thread, time::{Duration, Instant}};
+use crate::{accumulate::{Accumulate, Arithmetic, Overflowed}, big_uint::BigUint, executor::{Executor, Splittable, SyncExecutor, ThreadExecutor}, input::{ByteRanges, Input, Lines, Splitter}, shuffle::{shuffle_into, HashPartitioner, Partitioner, RangePartitioner}, progress::{Counters, JobEvent, JobHandle, Progress}, reorder::ReorderBuffer, shutdown::CancellationToken, spill::{read_run, write_run, Merge, Run, RunFile, Spill}, thread_pool::ThreadPool};
 
 const  DATA: &str = "86967897737416471853297327050364959
     11861322575564723963297542624962850
@@ -8,117 +9,1638 @@ const  DATA: &str = "86967897737416471853297327050364959
     16278424637452589860345374828574668"
 ;
 
-/// for larger data (>= 7rows) async version is faster !!
-pub fn map_reduce_sync() {
+/// Collects the `(key, value)` pairs a map function emits for one record.
+pub struct Emitter<K, V> {
+    pairs: Vec<(K, V)>,
+}
 
-    let mut children: Vec<u32> = vec![];
-    let chunked_data = DATA.split_whitespace();
+impl<K, V> Emitter<K, V> {
+    pub fn emit(&mut self, key: K, value: V) {
+        self.pairs.push((key, value));
+    }
+}
 
-    for (i, data_segment) in chunked_data.enumerate() {
-        println!("data segment {} is \"{}\"", i, data_segment);
+type MapFn<I, K, V, E> = Arc<dyn Fn(I, &mut Emitter<K, V>) -> Result<(), E> + Send + Sync>;
+type ReduceFn<K, V, E> = Arc<dyn Fn(&K, Vec<V>) -> Result<V, E> + Send + Sync>;
+type CombineFn<K, V, E> = Arc<dyn Fn(&K, Vec<V>) -> Result<V, E> + Send + Sync>;
+type RecordBytesFn<I> = Arc<dyn Fn(&I) -> usize + Send + Sync>;
+/// Every reducer's output, the failures the job got over and the number of runs it spilled.
+type JobOutput<K, V, E> = Result<(Vec<BTreeMap<K, V>>, Vec<SegmentFailure<E>>, usize), JobError<E>>;
+
+
+/// What went wrong in a map or reduce function.
+#[derive(Debug)]
+pub enum TaskError<E> {
+    Failed(E),
+    /// The function panicked, with the panic message.
+    Panicked(String),
+    /// Writing or reading back a spilled run failed, see [`MapReduce::spill_to`].
+    Spill(io::Error),
+    /// The worker process running the task crashed or didn't answer in time, see [`Coordinator`](crate::cluster::Coordinator).
+    WorkerLost(String),
+    /// The job was cancelled before the task was done, see [`JobHandle::cancel`].
+    Cancelled,
+}
+
+impl<E: fmt::Display> fmt::Display for TaskError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Failed(error) => write!(f, "{}", error),
+            TaskError::Panicked(message) => write!(f, "panicked: {}", message),
+            TaskError::Spill(error) => write!(f, "spill failed: {}", error),
+            TaskError::WorkerLost(reason) => write!(f, "worker lost: {}", reason),
+            TaskError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Map,
+    Reduce,
+}
+
+/// ### One failed segment (or reducer) of a job.
+/// - `Map`: `index` is the segment, counted from 0 in input order, `record` the record's position in the input
+///   (`None` if the segment was given up on without a record failing, i.e. after another segment failed).
+/// - `Reduce`: `index` is the reducer, whose input may also have failed to spill.
+#[derive(Debug)]
+pub struct SegmentFailure<E> {
+    pub phase: Phase,
+    pub index: usize,
+    pub record: Option<usize>,
+    /// 1 for the first attempt, more with [`MapReduce::retry_segments`].
+    pub attempt: u32,
+    pub error: TaskError<E>,
+}
+
+impl<E: fmt::Display> fmt::Display for SegmentFailure<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.phase, self.record) {
+            (Phase::Map, Some(record)) => write!(f, "segment {}, record {}", self.index, record)?,
+            (Phase::Map, None) => write!(f, "segment {}", self.index)?,
+            (Phase::Reduce, _) => write!(f, "reducer {}", self.index)?,
+        }
+        write!(f, " (attempt {}): {}", self.attempt, self.error)
+    }
+}
+
+/// The output of a job which went through, with the failures it got over (skipped records, failed attempts).
+#[derive(Debug)]
+pub struct JobReport<K, V, E> {
+    pub output: BTreeMap<K, V>,
+    pub failures: Vec<SegmentFailure<E>>,
+    /// Sorted runs written to disk, 0 unless the job has [`MapReduce::spill_to`].
+    pub spilled_runs: usize,
+}
+
+/// A job which didn't go through, every failure up to the one it gave up on, in segment order.
+#[derive(Debug)]
+pub struct JobError<E> {
+    pub failures: Vec<SegmentFailure<E>>,
+}
+
+impl<E: fmt::Display> fmt::Display for JobError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "map-reduce job failed")?;
+        for failure in &self.failures {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> Error for JobError<E> {}
+
+/// ### What a job does about a record whose map function fails (returns `Err` or panics).
+/// - `FailFast`: the job fails, segments still running stop at their next record.
+/// - `Skip`: the record's output is dropped and the job goes on, until more than `max_records` were skipped.
+/// - `Retry`: the whole segment is mapped again, `attempts` times at most, then the job fails.
+///   `clone` makes the copy of the records the next attempt needs.
+enum OnError<I> {
+    FailFast,
+    Skip { max_records: usize },
+    Retry { attempts: u32, clone: fn(&I) -> I },
+}
+
+impl<I> Clone for OnError<I> {
+    fn clone(&self) -> Self {
+        match *self {
+            OnError::FailFast => OnError::FailFast,
+            OnError::Skip { max_records } => OnError::Skip { max_records },
+            OnError::Retry { attempts, clone } => OnError::Retry { attempts, clone },
+        }
+    }
+}
+
+/// ## A map-reduce job over any iterator of input records `I`.
+/// - "Map": the records are cut into segments of [`MapReduce::segment_size`] records, one map task per segment.
+///   The map function turns a record into any number of `(K, V)` pairs through the [`Emitter`].
+///   The input is read lazily, [`MapReduce::read_ahead`] segments at a time, mapped and shuffled before reading on,
+///   so an input bigger than memory (see [`Input`]) works as long as its map output (after combining) fits,
+///   or spills to disk (see [`MapReduce::spill_to`]).
+/// - "Combine" (optional): right after its segment, every key's values are combined into one,
+///   so less has to be kept around and shuffled (the digit sum of a segment instead of its digits).
+/// - "Shuffle": the [`Partitioner`] routes every key to one of [`MapReduce::reducers`] reducers,
+///   which gets the values of its keys from all segments, grouped by key and in segment order.
+/// - "Reduce": one task per reducer, every key's values are reduced to the final value.
+///
+/// Where the tasks run is up to the [`Executor`] given to [`MapReduce::run_on`], [`MapReduce::run`] is the
+/// [`SyncExecutor`], [`MapReduce::run_async`] runs them on tokio's blocking pool. An executor may also split
+/// big segments further (see `WorkStealingExecutor`). The result is the same either way.
+/// ```ignore
+/// let word_count = MapReduce::fold(|line: &str, out| line.split_whitespace().for_each(|word| out.emit(word, 1)), |a, b| a + b);
+/// let counts = word_count.run_on(&ThreadExecutor::default(), text.lines());
+/// ```
+///
+/// Map and reduce functions built with [`MapReduce::try_new`] return a `Result<_, E>`, the `try_run*` methods report
+/// every failure (and every panic) with its segment, see [`MapReduce::skip_bad_records`] and
+/// [`MapReduce::retry_segments`] for getting over them. The `run*` methods are for jobs which can't fail,
+/// a panic in one of their functions is resumed on the calling thread.
+///
+/// [`MapReduce::spawn_on`] runs a job in the background, with its [`Progress`] and a way to cancel it,
+/// [`MapReduce::on_event`] tells about every segment and reducer as it starts, finishes or fails.
+pub struct MapReduce<I, K, V, E = Infallible> {
+    map: MapFn<I, K, V, E>,
+    combine: Option<CombineFn<K, V, E>>,
+    reduce: ReduceFn<K, V, E>,
+    segment_size: usize,
+    read_ahead: usize,
+    partitioner: Arc<dyn Partitioner<K>>,
+    reducers: usize,
+    on_error: OnError<I>,
+    spill: Option<SpillTo<K, V>>,
+    events: Option<Events<E>>,
+    record_bytes: Option<RecordBytesFn<I>>,
+    expected_bytes: Option<u64>,
+    ordered: Option<usize>,
+}
+
+impl<I, K, V, E> Clone for MapReduce<I, K, V, E> {
+    fn clone(&self) -> Self {
+        MapReduce {
+            map: Arc::clone(&self.map),
+            combine: self.combine.clone(),
+            reduce: Arc::clone(&self.reduce),
+            segment_size: self.segment_size,
+            read_ahead: self.read_ahead,
+            partitioner: Arc::clone(&self.partitioner),
+            reducers: self.reducers,
+            on_error: self.on_error.clone(),
+            spill: self.spill.clone(),
+            events: self.events.clone(),
+            record_bytes: self.record_bytes.clone(),
+            expected_bytes: self.expected_bytes,
+            ordered: self.ordered,
+        }
+    }
+}
+
+/// The callback of [`MapReduce::on_event`], `describe` is `E`'s `Display` for the [`JobEvent::Failed`] message.
+struct Events<E> {
+    callback: Arc<dyn Fn(JobEvent) + Send + Sync>,
+    describe: fn(&TaskError<E>) -> String,
+}
+
+impl<E> Clone for Events<E> {
+    fn clone(&self) -> Self {
+        Events { callback: Arc::clone(&self.callback), describe: self.describe }
+    }
+}
+
+/// ### Where the partitions go once they hold more than `max_values` values.
+/// Every partition is written to a sorted run in `directory` and emptied, the reducer merges its runs back.
+/// `write` and `read` are [`write_run`] and [`read_run`] for `K` and `V`, which only need to be [`Spill`] for it.
+struct SpillTo<K, V> {
+    directory: PathBuf,
+    max_values: usize,
+    write: fn(&Path, &BTreeMap<K, Vec<V>>) -> io::Result<RunFile>,
+    read: fn(RunFile) -> io::Result<Run<K, V>>,
+}
+
+impl<K, V> Clone for SpillTo<K, V> {
+    fn clone(&self) -> Self {
+        SpillTo { directory: self.directory.clone(), max_values: self.max_values, write: self.write, read: self.read }
+    }
+}
+
+/// A run of records, `offset` is the position of its first record in the input.
+pub(crate) struct Segment<I> {
+    pub(crate) index: usize,
+    pub(crate) offset: usize,
+    pub(crate) records: Vec<I>,
+}
+
+impl<I> Splittable for Segment<I> {
+
+    fn size(&self) -> usize {
+        self.records.len()
+    }
+
+    fn split(self) -> (Self, Self) {
+        let (first, second) = self.records.split();
+        let offset = self.offset + first.len();
+        (Segment { records: first, ..self }, Segment { index: self.index, offset, records: second })
+    }
+}
+
+/// What a map task hands back, `failed` if the job can't go on.
+pub(crate) struct MapOutput<K, V, E> {
+    pub(crate) index: usize,
+    pub(crate) grouped: BTreeMap<K, Vec<V>>,
+    pub(crate) failures: Vec<SegmentFailure<E>>,
+    pub(crate) failed: bool,
+}
+
+/// Shared by the tasks of one run, and by its [`JobHandle`].
+#[derive(Default)]
+pub(crate) struct JobState {
+    aborted: AtomicBool,
+    skipped: AtomicUsize,
+    pub(crate) cancel: CancellationToken,
+    pub(crate) progress: Counters,
+    ordered: Option<Ordered>,
+}
+
+impl JobState {
+    /// Whether the segment `index` should stop: the job was cancelled or failed elsewhere
+    /// (in [`MapReduce::ordered`] mode: in a segment before it).
+    fn stopping(&self, index: usize) -> bool {
+        self.cancel.is_cancelled() || match &self.ordered {
+            Some(ordered) => index > ordered.first_failed.load(Ordering::SeqCst),
+            None => self.aborted.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// What [`MapReduce::ordered`] needs of a run: the reorder buffers of both phases, and where the job failed.
+pub(crate) struct Ordered {
+    map: ReorderBuffer<Released>,
+    reduce: ReorderBuffer<Vec<JobEvent>>,
+    /// The first segment which fails the job, `usize::MAX` while none does.
+    first_failed: AtomicUsize,
+    /// How many of that segment's failures the job got to.
+    kept: AtomicUsize,
+}
+
+impl Ordered {
+    fn new(window: usize) -> Self {
+        Ordered {
+            map: ReorderBuffer::new(window),
+            reduce: ReorderBuffer::new(usize::MAX),
+            first_failed: AtomicUsize::new(usize::MAX),
+            kept: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A mapped segment waiting for the ones before it.
+struct Released {
+    events: Vec<JobEvent>,
+    failures: usize,
+    failed: bool,
+}
+
+impl<I, K, V> MapReduce<I, K, V> where I: Send, K: Ord + Hash + Send, V: Send, {
+
+    /// One record per segment, no combiner, and keys hashed to a reducer per core.
+    pub fn new(
+        map: impl Fn(I, &mut Emitter<K, V>) + Send + Sync + 'static,
+        reduce: impl Fn(&K, Vec<V>) -> V + Send + Sync + 'static,
+    ) -> Self {
+        Self::try_new(
+            move |record, out| {
+                map(record, out);
+                Ok(())
+            },
+            move |key, values| Ok(reduce(key, values)),
+        )
+    }
+
+    /// For a reduce which just folds two values into one (a sum, a max, ...), it's the combiner as well.
+    pub fn fold(
+        map: impl Fn(I, &mut Emitter<K, V>) + Send + Sync + 'static,
+        fold: impl Fn(V, V) -> V + Send + Sync + 'static,
+    ) -> Self {
+        let fold = Arc::new(fold);
+        let reduce = Arc::clone(&fold);
+        Self::try_new(
+            move |record, out| {
+                map(record, out);
+                Ok(())
+            },
+            move |_, values| Ok(fold_values(&*reduce, values)),
+        )
+        .combine(move |_, values| fold_values(&*fold, values))
+    }
+
+    /// Everything on the calling thread.
+    pub fn run(&self, inputs: impl IntoIterator<Item = I>) -> BTreeMap<K, V> {
+        self.run_on(&SyncExecutor, inputs)
+    }
+
+    /// All reducers' output in one map.
+    pub fn run_on(&self, executor: &impl Executor, inputs: impl IntoIterator<Item = I>) -> BTreeMap<K, V> {
+        self.run_partitioned_on(executor, inputs).into_iter().flatten().collect()
+    }
+
+    /// The output of every reducer by itself, like the `part-r-0000N` files of a Hadoop job.
+    pub fn run_partitioned_on(&self, executor: &impl Executor, inputs: impl IntoIterator<Item = I>) -> Vec<BTreeMap<K, V>> {
+        infallible(self.run_job(executor, inputs, &self.job_state())).0
+    }
+
+    /// Reads the records to stop at the first error, the part of the input before it is still mapped but not reduced.
+    pub fn run_input_on(&self, executor: &impl Executor, input: impl IntoIterator<Item = io::Result<I>>) -> io::Result<BTreeMap<K, V>> {
+        let mut error = None;
+        let records = input.into_iter().map_while(|record| record.map_err(|failed| error = Some(failed)).ok());
+        let partitions = self.run_partitioned_on(executor, records);
+        match error {
+            Some(error) => Err(error),
+            None => Ok(partitions.into_iter().flatten().collect()),
+        }
+    }
+}
+
+impl<I, K, V, E> MapReduce<I, K, V, E> where I: Send, K: Ord + Hash + Send, V: Accumulate + Send + 'static, E: From<Overflowed> + Send, {
+
+    /// ## The sum of every key's values, `arithmetic` says what happens if it doesn't fit `V`.
+    /// Every segment is combined into its sums as well, a key takes one value per segment, and one after the shuffle.
+    /// With [`Arithmetic::Checked`] a segment whose own sum doesn't fit fails with [`Overflowed`] right there.
+    /// A sum which may not fit any integer type is a job for [`BigUint`], which never overflows.
+    pub fn try_sum(map: impl Fn(I, &mut Emitter<K, V>) -> Result<(), E> + Send + Sync + 'static, arithmetic: Arithmetic) -> Self {
+        Self::accumulate(map, arithmetic, Arithmetic::add)
+    }
+
+    /// The product of every key's values, the same as [`MapReduce::try_sum`] otherwise.
+    pub fn try_product(map: impl Fn(I, &mut Emitter<K, V>) -> Result<(), E> + Send + Sync + 'static, arithmetic: Arithmetic) -> Self {
+        Self::accumulate(map, arithmetic, Arithmetic::mul)
+    }
+
+    fn accumulate(
+        map: impl Fn(I, &mut Emitter<K, V>) -> Result<(), E> + Send + Sync + 'static,
+        arithmetic: Arithmetic,
+        operation: fn(Arithmetic, V, V) -> Result<V, Overflowed>,
+    ) -> Self {
+        // the reduce and the combiner alike, only checked arithmetic fails
+        let fold = move |_: &K, values: Vec<V>| {
+            let mut values = values.into_iter();
+            let first = values.next().expect("every key has at least one value");
+            values.try_fold(first, |total, value| operation(arithmetic, total, value)).map_err(E::from)
+        };
+        Self::try_new(map, fold).try_combine(fold)
+    }
+}
+
+fn fold_values<V>(fold: &impl Fn(V, V) -> V, values: Vec<V>) -> V {
+    values.into_iter().reduce(fold).expect("every key has at least one value")
+}
+
+/// The only failures of a job which can't fail are panics, they go on unwinding here, a failed spill panics.
+fn infallible<T>(result: Result<T, JobError<Infallible>>) -> T {
+    result.unwrap_or_else(|error| {
+        let message = error.failures.into_iter()
+            .next()
+            .map(|failure| match failure.error {
+                TaskError::Panicked(message) => message,
+                TaskError::Failed(never) => match never {},
+                TaskError::Spill(error) => format!("map-reduce spill failed: {}", error),
+                TaskError::WorkerLost(reason) => format!("map-reduce worker lost: {}", reason),
+                TaskError::Cancelled => "map-reduce job cancelled".to_string(),
+            })
+            .unwrap_or_default();
+        panic::resume_unwind(Box::new(message))
+    })
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("Box<dyn Any>".to_string(), |message| message.to_string()),
+    }
+}
+
+impl<I, K, V, E> MapReduce<I, K, V, E> where I: Send, K: Ord + Hash + Send, V: Send, E: Send, {
+
+    /// Map and reduce functions which can fail, see the `try_run*` methods.
+    pub fn try_new(
+        map: impl Fn(I, &mut Emitter<K, V>) -> Result<(), E> + Send + Sync + 'static,
+        reduce: impl Fn(&K, Vec<V>) -> Result<V, E> + Send + Sync + 'static,
+    ) -> Self {
+        MapReduce {
+            map: Arc::new(map),
+            combine: None,
+            reduce: Arc::new(reduce),
+            segment_size: 1,
+            read_ahead: 256,
+            partitioner: Arc::new(HashPartitioner),
+            reducers: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            on_error: OnError::FailFast,
+            spill: None,
+            events: None,
+            record_bytes: None,
+            expected_bytes: None,
+            ordered: None,
+        }
+    }
+
+    /// Runs on the values of every key within one segment, before the shuffle.
+    pub fn combine(mut self, combine: impl Fn(&K, Vec<V>) -> V + Send + Sync + 'static) -> Self {
+        self.combine = Some(Arc::new(move |key, values| Ok(combine(key, values))));
+        self
+    }
+
+    /// A combiner which can fail, e.g. a checked sum. Its error fails the segment like a map function's,
+    /// or, once the shuffle combines what the segments left for a key, the key's reducer.
+    pub fn try_combine(mut self, combine: impl Fn(&K, Vec<V>) -> Result<V, E> + Send + Sync + 'static) -> Self {
+        self.combine = Some(Arc::new(combine));
+        self
+    }
+
+    /// Records per map task, more means less overhead per record but fewer tasks to spread over the threads.
+    pub fn segment_size(mut self, records: usize) -> Self {
+        self.segment_size = records.max(1);
+        self
+    }
+
+    /// Segments read and mapped at a time (256 unless told otherwise), the records of the next ones aren't read
+    /// before these are mapped. More keeps more threads busy at the end of a batch, less keeps less input in memory.
+    pub fn read_ahead(mut self, segments: usize) -> Self {
+        self.read_ahead = segments.max(1);
+        self
+    }
+
+    /// Number of reduce tasks, and of maps [`MapReduce::run_partitioned_on`] returns.
+    pub fn reducers(mut self, reducers: usize) -> Self {
+        self.reducers = reducers.max(1);
+        self
+    }
+
+    pub fn partitioner(mut self, partitioner: impl Partitioner<K> + 'static) -> Self {
+        self.partitioner = Arc::new(partitioner);
+        self
+    }
+
+    /// The first failing record fails the job, that's the default.
+    pub fn fail_fast(mut self) -> Self {
+        self.on_error = OnError::FailFast;
+        self
+    }
+
+    /// Records whose map fails are left out and reported, the job only fails on the one after `max_records`.
+    pub fn skip_bad_records(mut self, max_records: usize) -> Self {
+        self.on_error = OnError::Skip { max_records };
+        self
+    }
+
+    /// Maps a failed segment again from the start, for errors which go away (a flaky lookup, a busy service),
+    /// `attempts` in total before the job fails. Every attempt but the last maps a clone of the records.
+    pub fn retry_segments(mut self, attempts: u32) -> Self where I: Clone {
+        self.on_error = OnError::Retry { attempts: attempts.max(1), clone: I::clone };
+        self
+    }
+
+    /// ## External sort of the map output, for jobs whose intermediate data doesn't fit into memory.
+    /// After a batch of segments ([`MapReduce::read_ahead`]) was shuffled, if the partitions hold more than `max_values`
+    /// values (after combining), every partition is written to a temporary file in `directory`, sorted by key, and emptied.
+    /// Each reducer then k-way merges its runs and what's left in memory, one key at a time, so only the current key
+    /// of every run is in memory. The files are deleted once merged, or when the job fails.
+    ///
+    /// Memory is counted in values rather than bytes, `max_values` should leave room for a batch's map output on top.
+    /// The output of the reducers still has to fit.
+    pub fn spill_to(mut self, directory: impl Into<PathBuf>, max_values: usize) -> Self where K: Spill + 'static, V: Spill + 'static {
+        self.spill = Some(SpillTo { directory: directory.into(), max_values, write: write_run, read: read_run });
+        self
+    }
+
+    /// Called with every [`JobEvent`] of a run, from the threads running its tasks.
+    pub fn on_event(mut self, callback: impl Fn(JobEvent) + Send + Sync + 'static) -> Self where E: fmt::Display {
+        self.events = Some(Events { callback: Arc::new(callback), describe: |error| error.to_string() });
+        self
+    }
+
+    /// The size of a record, for the bytes of [`Progress`], e.g. `|line: &String| line.len() + 1` for lines.
+    pub fn record_bytes(mut self, bytes: impl Fn(&I) -> usize + Send + Sync + 'static) -> Self {
+        self.record_bytes = Some(Arc::new(bytes));
+        self
+    }
+
+    /// The size of the whole input in [`MapReduce::record_bytes`], for [`Progress::eta`] before the input was read
+    /// to its end, e.g. from [`Input::total_bytes`].
+    pub fn expected_bytes(mut self, bytes: u64) -> Self {
+        self.expected_bytes = Some(bytes);
+        self
+    }
+
+    /// ## Deterministic mode: a run's events and failures only depend on its input, not on which task finished first.
+    /// - Map segments are released in input order through a reorder buffer, a segment which finished before the
+    ///   ones before it waits there, and one more than `window` segments ahead doesn't start until they are done.
+    ///   [`JobEvent`]s of the map phase come in input order, the reduce phase's in reducer order.
+    /// - The job fails at the first segment (in input order) which fails, with the failures up to that one,
+    ///   `skip_bad_records` skips the first failing records in input order, and segments after a failed one stop.
+    /// - Segments aren't split (see `WorkStealingExecutor`) and go to the executor `window` at a time, so none of them
+    ///   ever waits for a turn it can't get, whichever order the executor starts them in (a static split or work
+    ///   stealing starts every thread on its own contiguous share). No more than `window` segments are mapped at once.
+    ///
+    /// The output doesn't need it, it's the same on every run and executor anyway.
+    pub fn ordered(mut self, window: usize) -> Self {
+        self.ordered = Some(window.max(1));
+        self
+    }
+
+    fn job_state(&self) -> JobState {
+        JobState { ordered: self.ordered.map(Ordered::new), ..JobState::default() }
+    }
+
+    pub fn try_run(&self, inputs: impl IntoIterator<Item = I>) -> Result<JobReport<K, V, E>, JobError<E>> {
+        self.try_run_on(&SyncExecutor, inputs)
+    }
+
+    pub fn try_run_on(&self, executor: &impl Executor, inputs: impl IntoIterator<Item = I>) -> Result<JobReport<K, V, E>, JobError<E>> {
+        let (partitions, failures, spilled_runs) = self.run_job(executor, inputs, &self.job_state())?;
+        Ok(JobReport { output: partitions.into_iter().flatten().collect(), failures, spilled_runs })
+    }
+
+    fn run_job(&self, executor: &impl Executor, inputs: impl IntoIterator<Item = I>, state: &JobState) -> JobOutput<K, V, E> {
+
+        let mut failures = Vec::new();
+        state.progress.reducers(self.reducers);
+
+        /*************************************************************************
+         * "Map" phase
+         *
+         * Divide our data into segments, and apply initial processing
+         ************************************************************************/
+        let (mut inputs, mut next_segment) = (inputs.into_iter(), 0);
+        let (mut partitions, mut runs) = (self.partitions(), self.runs());
+        loop {
+            self.check_cancelled(state, &mut failures, Phase::Map, next_segment)?;
+            let segments = self.read_segments(&mut inputs, &mut next_segment, state);
+            if segments.is_empty() {
+                break;
+            }
+            // an executor which splits segments (work stealing) maps the pieces separately and joins them back,
+            // in ordered mode segments go whole, their turn in the reorder buffer is by segment
+            let mapped = match self.ordered {
+                Some(window) => batches(segments, window).into_iter()
+                    .flat_map(|batch| executor.execute(batch, |segment| self.map_segment(segment, state)))
+                    .collect(),
+                None => executor.execute_splittable(
+                    segments,
+                    |segment| self.map_segment(segment, state),
+                    |first, second| self.join_outputs(first, second),
+                ),
+            };
+            self.collect_mapped(&mut partitions, &mut failures, mapped, state)?;
+            if self.over_spill_limit(&partitions) {
+                self.spill(&mut partitions, &mut runs).map_err(|failure| job_failed(&mut failures, failure))?;
+            }
+        }
+
+        /*************************************************************************
+         * "Reduce" phase
+         *
+         * Collect our intermediate results, and combine them into a final result
+         ************************************************************************/
+        self.check_cancelled(state, &mut failures, Phase::Reduce, 0)?;
+        let spilled_runs = runs.iter().map(Vec::len).sum();
+        let reduced = executor.execute(
+            partitions.into_iter().zip(runs).enumerate().collect(),
+            |(reducer, (partition, runs))| self.reduce_partition(reducer, partition, runs, state),
+        );
+        Self::collect_reduced(failures, reduced, spilled_runs)
+    }
+
+    pub(crate) fn partitions(&self) -> Vec<BTreeMap<K, Vec<V>>> {
+        (0..self.reducers).map(|_| BTreeMap::new()).collect()
+    }
+
+    /// The runs every reducer spilled, oldest first.
+    fn runs(&self) -> Vec<Vec<RunFile>> {
+        (0..self.reducers).map(|_| Vec::new()).collect()
+    }
+
+    fn over_spill_limit(&self, partitions: &[BTreeMap<K, Vec<V>>]) -> bool {
+        self.spill.as_ref().is_some_and(|spill| partitions.iter().flat_map(BTreeMap::values).map(Vec::len).sum::<usize>() > spill.max_values)
+    }
+
+    /// Writes every partition with anything in it to a run of its reducer and empties it.
+    fn spill(&self, partitions: &mut [BTreeMap<K, Vec<V>>], runs: &mut [Vec<RunFile>]) -> Result<(), SegmentFailure<E>> {
+        let Some(spill) = &self.spill else {
+            return Ok(());
+        };
+        for (reducer, (partition, runs)) in partitions.iter_mut().zip(runs).enumerate().filter(|(_, (partition, _))| !partition.is_empty()) {
+            match (spill.write)(&spill.directory, partition) {
+                Ok(run) => runs.push(run),
+                Err(error) => return Err(SegmentFailure { phase: Phase::Reduce, index: reducer, record: None, attempt: 1, error: TaskError::Spill(error) }),
+            }
+            partition.clear();
+        }
+        Ok(())
+    }
+
+    /// [`MapReduce::segments`], counted for the progress.
+    fn read_segments(&self, inputs: &mut impl Iterator<Item = I>, next_segment: &mut usize, state: &JobState) -> Vec<Segment<I>> {
+        let segments = self.segments(inputs, next_segment);
+        segments.iter().for_each(|segment| state.progress.read(segment.index, segment.records.len()));
+        if segments.len() < self.read_ahead {
+            state.progress.input_done(*next_segment);
+        }
+        segments
+    }
+
+    /// Between batches of tasks: a cancelled job fails with `Cancelled` for the segment (or reducer) it stopped at.
+    fn check_cancelled(&self, state: &JobState, failures: &mut Vec<SegmentFailure<E>>, phase: Phase, index: usize) -> Result<(), JobError<E>> {
+        if !state.cancel.is_cancelled() {
+            return Ok(());
+        }
+        let failure = SegmentFailure { phase, index, record: None, attempt: 1, error: TaskError::Cancelled };
+        self.failed_event(&failure);
+        Err(job_failed(failures, failure))
+    }
 
-        let result: u32 = data_segment
-            // iterate over the characters of our segment..
-            .chars()
-            // .. convert text-characters to their number value..
-            .map(|c| c.to_digit(10).expect("should be a digit"))
-            // .. and sum the resulting iterator of numbers
-            .sum()
-        ;
+    fn event(&self, event: JobEvent) {
+        if let Some(events) = &self.events {
+            (events.callback)(event);
+        }
+    }
+
+    fn failed_event(&self, failure: &SegmentFailure<E>) {
+        if let Some(event) = self.failure_event(failure) {
+            self.event(event);
+        }
+    }
+
+    /// `None` without [`MapReduce::on_event`].
+    fn failure_event(&self, failure: &SegmentFailure<E>) -> Option<JobEvent> {
+        self.events.as_ref().map(|events| JobEvent::Failed {
+            phase: failure.phase,
+            index: failure.index,
+            record: failure.record,
+            attempt: failure.attempt,
+            error: (events.describe)(&failure.error),
+        })
+    }
+
+    /// The next `read_ahead` segments of the input, empty once it's exhausted.
+    pub(crate) fn segments(&self, inputs: &mut impl Iterator<Item = I>, next_segment: &mut usize) -> Vec<Segment<I>> {
+        let mut segments = Vec::new();
+        while segments.len() < self.read_ahead {
+            let records = inputs.by_ref().take(self.segment_size).collect::<Vec<_>>();
+            if records.is_empty() {
+                break;
+            }
+            segments.push(Segment { index: *next_segment, offset: *next_segment * self.segment_size, records });
+            *next_segment += 1;
+        }
+        segments
+    }
+
+    /// The failures of a batch of map tasks go to the report, their output to the partitions, unless the job failed.
+    /// In ordered mode the job fails where `release` said, with the failures up to there.
+    fn collect_mapped(
+        &self,
+        partitions: &mut [BTreeMap<K, Vec<V>>],
+        failures: &mut Vec<SegmentFailure<E>>,
+        mapped: Vec<MapOutput<K, V, E>>,
+        state: &JobState,
+    ) -> Result<(), JobError<E>> {
+        let mut failed = false;
+        let mut grouped = Vec::with_capacity(mapped.len());
+        for output in mapped {
+            if let Some(ordered) = state.ordered.as_ref().filter(|ordered| ordered.first_failed.load(Ordering::SeqCst) == output.index) {
+                failures.extend(output.failures.into_iter().take(ordered.kept.load(Ordering::SeqCst)));
+                return Err(JobError { failures: std::mem::take(failures) });
+            }
+            failures.extend(output.failures);
+            failed |= output.failed;
+            grouped.push(output.grouped);
+        }
+        if failed {
+            return Err(JobError { failures: std::mem::take(failures) });
+        }
+        self.shuffle(partitions, grouped).map_err(|failure| job_failed(failures, failure))
+    }
+
+    fn collect_reduced(
+        mut failures: Vec<SegmentFailure<E>>,
+        reduced: Vec<Result<BTreeMap<K, V>, SegmentFailure<E>>>,
+        spilled_runs: usize,
+    ) -> JobOutput<K, V, E> {
+        let mut partitions = Vec::with_capacity(reduced.len());
+        let mut failed = false;
+        for result in reduced {
+            match result {
+                Ok(partition) => partitions.push(partition),
+                Err(failure) => {
+                    failures.push(failure);
+                    failed = true;
+                }
+            }
+        }
+        match failed {
+            true => Err(JobError { failures }),
+            false => Ok((partitions, failures, spilled_runs)),
+        }
+    }
+
+    /// Routes mapped segments to the partitions and combines what piled up for a key, so a key takes one value
+    /// of memory however many segments it came up in. A failing combiner fails the key's reducer.
+    pub(crate) fn shuffle(&self, partitions: &mut [BTreeMap<K, Vec<V>>], mapped: Vec<BTreeMap<K, Vec<V>>>) -> Result<(), SegmentFailure<E>> {
+        shuffle_into(partitions, mapped, &*self.partitioner);
+        for (reducer, partition) in partitions.iter_mut().enumerate() {
+            self.combine_values(partition)
+                .map_err(|error| SegmentFailure { phase: Phase::Reduce, index: reducer, record: None, attempt: 1, error: TaskError::Failed(error) })?;
+        }
+        Ok(())
+    }
+
+    /// Every key with more than one value down to one, if there is a combiner.
+    fn combine_values(&self, grouped: &mut BTreeMap<K, Vec<V>>) -> Result<(), E> {
+        if let Some(combine) = &self.combine {
+            for (key, values) in grouped.iter_mut().filter(|(_, values)| values.len() > 1) {
+                let combined = combine(key, std::mem::take(values))?;
+                values.push(combined);
+            }
+        }
+        Ok(())
+    }
+
+    /// A whole map task: waits for the segment's turn in an ordered job, maps it (see [`MapReduce::map_attempts`])
+    /// and reports how it went. Its progress counts right away, its events and whether it aborts the job go out
+    /// right away as well, or in an ordered job once the segments before it are released.
+    pub(crate) fn map_segment(&self, segment: Segment<I>, state: &JobState) -> MapOutput<K, V, E> {
+
+        let (index, size) = (segment.index, segment.records.len());
+        if let Some(ordered) = &state.ordered {
+            ordered.map.wait_turn(index);
+        }
+        if state.progress.start(index) && state.ordered.is_none() {
+            self.event(JobEvent::Started { phase: Phase::Map, index });
+        }
+
+        let output = self.map_attempts(segment, state);
+
+        let finished = !output.failed && state.progress.finish(index, size);
+        // a cancelled segment didn't fail, it's just not done
+        if output.failed && !state.cancel.is_cancelled() {
+            state.progress.fail(index);
+        }
+        match &state.ordered {
+            None => {
+                output.failures.iter().for_each(|failure| self.failed_event(failure));
+                if finished {
+                    self.event(JobEvent::Finished { phase: Phase::Map, index });
+                }
+                if output.failed {
+                    state.aborted.store(true, Ordering::SeqCst);
+                }
+            }
+            // the segment's events, and whether it fails the job, wait for the segments before it
+            Some(ordered) => {
+                let mut events = Vec::new();
+                if self.events.is_some() {
+                    events.push(JobEvent::Started { phase: Phase::Map, index });
+                    events.extend(output.failures.iter().filter_map(|failure| self.failure_event(failure)));
+                    if !output.failed {
+                        events.push(JobEvent::Finished { phase: Phase::Map, index });
+                    }
+                }
+                let released = Released { events, failures: output.failures.len(), failed: output.failed };
+                ordered.map.finish(index, released, |index, released| self.release(state, ordered, index, released));
+            }
+        }
+        output
+    }
+
+    /// The retry loop: the segment's records go through [`MapReduce::map_records`] until an attempt goes through,
+    /// `OnError::Retry` ran out of attempts (every attempt but the last gets a clone of the records)
+    /// or the job was aborted. Carries the failures of every attempt, and `failed` if none went through.
+    fn map_attempts(&self, segment: Segment<I>, state: &JobState) -> MapOutput<K, V, E> {
+
+        let Segment { index, offset, mut records } = segment;
+        let attempts = match self.on_error {
+            OnError::Retry { attempts, .. } => attempts,
+            _ => 1,
+        };
+        let mut failures = Vec::new();
+
+        for attempt in 1..=attempts {
+            let attempt_records = match &self.on_error {
+                OnError::Retry { clone, .. } if attempt < attempts => records.iter().map(clone).collect(),
+                _ => std::mem::take(&mut records),
+            };
+            match self.map_records(index, offset, attempt, attempt_records, state) {
+                Ok((grouped, skipped)) => {
+                    failures.extend(skipped);
+                    return MapOutput { index, grouped, failures, failed: false };
+                }
+                Err(failure) => {
+                    let aborted = failure.is_none();
+                    failures.extend(failure);
+                    if aborted {
+                        if state.cancel.is_cancelled() {
+                            failures.push(SegmentFailure { phase: Phase::Map, index, record: None, attempt, error: TaskError::Cancelled });
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        MapOutput { index, grouped: BTreeMap::new(), failures, failed: true }
+    }
+
+    /// In input order: the job fails at the first segment which failed, or whose skipped records are one too many.
+    /// Its events go out up to that failure, no segment's after it do.
+    fn release(&self, state: &JobState, ordered: &Ordered, index: usize, released: Released) {
+
+        if ordered.first_failed.load(Ordering::SeqCst) != usize::MAX {
+            return;
+        }
+        let (mut kept, mut failed) = (released.failures, released.failed);
+        if let OnError::Skip { max_records } = self.on_error {
+            let skipped = released.failures - released.failed as usize;
+            let before = state.skipped.fetch_add(skipped, Ordering::SeqCst);
+            if before + skipped > max_records {
+                (kept, failed) = (max_records - before + 1, true);
+            }
+        }
+        if failed {
+            ordered.kept.store(kept, Ordering::SeqCst);
+            ordered.first_failed.store(index, Ordering::SeqCst);
+        }
+
+        let mut failures = 0;
+        for event in released.events {
+            match event {
+                JobEvent::Failed { .. } if failures == kept => continue,
+                JobEvent::Failed { .. } => failures += 1,
+                JobEvent::Finished { .. } if failed => continue,
+                _ => {}
+            }
+            self.event(event);
+        }
+    }
+
+    /// One attempt at a segment: its values grouped by key (combined if there is a combiner) and the skipped records,
+    /// or the failure which ended it, `None` if it stopped because the job failed elsewhere or was cancelled.
+    #[allow(clippy::type_complexity)]
+    fn map_records(
+        &self,
+        index: usize,
+        offset: usize,
+        attempt: u32,
+        records: Vec<I>,
+        state: &JobState,
+    ) -> Result<(BTreeMap<K, Vec<V>>, Vec<SegmentFailure<E>>), Option<SegmentFailure<E>>> {
+
+        let mut emitter = Emitter { pairs: Vec::new() };
+        let mut skipped = Vec::new();
+        for (position, record) in records.into_iter().enumerate() {
+            if state.stopping(index) {
+                return Err(None);
+            }
+            if let Some(bytes) = self.record_bytes.as_ref().filter(|_| attempt == 1) {
+                state.progress.add_bytes(bytes(&record));
+            }
+            let emitted = emitter.pairs.len();
+            let error = match panic::catch_unwind(AssertUnwindSafe(|| (self.map)(record, &mut emitter))) {
+                Ok(Ok(())) => continue,
+                Ok(Err(error)) => TaskError::Failed(error),
+                Err(payload) => TaskError::Panicked(panic_message(payload)),
+            };
+            // whatever the record emitted before failing is dropped with it
+            emitter.pairs.truncate(emitted);
+            let failure = SegmentFailure { phase: Phase::Map, index, record: Some(offset + position), attempt, error };
+            // in ordered mode a segment only counts its own, the job's count goes in input order (see `release`)
+            let skip = match self.on_error {
+                OnError::Skip { max_records } if state.ordered.is_some() => skipped.len() < max_records,
+                OnError::Skip { max_records } => state.skipped.fetch_add(1, Ordering::SeqCst) < max_records,
+                _ => false,
+            };
+            match skip {
+                true => skipped.push(failure),
+                false => return Err(Some(failure)),
+            }
+        }
+
+        let mut grouped = BTreeMap::<K, Vec<V>>::new();
+        for (key, value) in emitter.pairs {
+            grouped.entry(key).or_default().push(value);
+        }
+        self.combine_values(&mut grouped)
+            .map_err(|error| SegmentFailure { phase: Phase::Map, index, record: None, attempt, error: TaskError::Failed(error) })?;
+        Ok((grouped, skipped))
+    }
+
+    /// The map output of two consecutive pieces of a segment, as if the segment had been mapped in one go.
+    fn join_outputs(&self, mut first: MapOutput<K, V, E>, second: MapOutput<K, V, E>) -> MapOutput<K, V, E> {
+        first.failures.extend(second.failures);
+        first.failed |= second.failed;
+        for (key, values) in second.grouped {
+            first.grouped.entry(key).or_default().extend(values);
+        }
+        if let Err(error) = self.combine_values(&mut first.grouped) {
+            let failure = SegmentFailure { phase: Phase::Map, index: first.index, record: None, attempt: 1, error: TaskError::Failed(error) };
+            first.failures.push(failure);
+            first.failed = true;
+            first.grouped.clear();
+        }
+        first
+    }
+
+    /// Merges the reducer's spilled runs with what it has in memory (the newest values) and reduces key by key.
+    pub(crate) fn reduce_partition(&self, reducer: usize, partition: BTreeMap<K, Vec<V>>, runs: Vec<RunFile>, state: &JobState) -> Result<BTreeMap<K, V>, SegmentFailure<E>> {
+        let started = JobEvent::Started { phase: Phase::Reduce, index: reducer };
+        if state.ordered.is_none() {
+            self.event(started.clone());
+        }
+        let reduced = self.reduce_runs(reducer, partition, runs, state);
+        let ended = match &reduced {
+            Ok(_) => {
+                state.progress.reducer_done();
+                Some(JobEvent::Finished { phase: Phase::Reduce, index: reducer })
+            }
+            Err(failure) => self.failure_event(failure),
+        };
+        match &state.ordered {
+            None => ended.into_iter().for_each(|event| self.event(event)),
+            Some(ordered) => {
+                let events = std::iter::once(started).chain(ended).collect();
+                ordered.reduce.finish(reducer, events, |_, events: Vec<JobEvent>| events.into_iter().for_each(|event| self.event(event)));
+            }
+        }
+        reduced
+    }
+
+    fn reduce_runs(&self, reducer: usize, partition: BTreeMap<K, Vec<V>>, runs: Vec<RunFile>, state: &JobState) -> Result<BTreeMap<K, V>, SegmentFailure<E>> {
+        let failure = |error| SegmentFailure { phase: Phase::Reduce, index: reducer, record: None, attempt: 1, error };
+
+        let read = self.spill.as_ref().map(|spill| spill.read);
+        let mut sources = Vec::with_capacity(runs.len() + 1);
+        for run in runs {
+            sources.push(read.expect("only a job which spills has runs")(run).map_err(|error| failure(TaskError::Spill(error)))?);
+        }
+        sources.push(Run::Memory(partition.into_iter()));
 
-        children.push(result);
-        println!("processed segment {}, result={}", i, result);
+        Merge::new(sources).map_err(|error| failure(TaskError::Spill(error)))?.map(|entry| {
+            if state.cancel.is_cancelled() {
+                return Err(failure(TaskError::Cancelled));
+            }
+            let (key, values) = entry.map_err(|error| failure(TaskError::Spill(error)))?;
+            let error = match panic::catch_unwind(AssertUnwindSafe(|| (self.reduce)(&key, values))) {
+                Ok(Ok(value)) => return Ok((key, value)),
+                Ok(Err(error)) => TaskError::Failed(error),
+                Err(payload) => TaskError::Panicked(panic_message(payload)),
+            };
+            Err(failure(error))
+        }).collect()
     }
+}
+
+impl<I, K, V> MapReduce<I, K, V> where I: Send + 'static, K: Ord + Hash + Send + 'static, V: Send + 'static, {
+
+    /// The map and reduce tasks run on tokio's blocking pool, the awaiting task doesn't block its worker.
+    /// Must be called from inside a tokio runtime, a panicking task is resumed here.
+    pub async fn run_async(&self, inputs: impl IntoIterator<Item = I>) -> BTreeMap<K, V> {
+        infallible(self.run_job_async(inputs).await).0.into_iter().flatten().collect()
+    }
+}
+
+impl<I, K, V, E> MapReduce<I, K, V, E> where I: Send + 'static, K: Ord + Hash + Send + 'static, V: Send + 'static, E: Send + 'static, {
+
+    /// [`MapReduce::try_run_on`] on a thread of its own, the [`JobHandle`] shows its [`Progress`] and can cancel it.
+    /// ```ignore
+    /// let job = word_count().record_bytes(|line: &String| line.len() + 1).expected_bytes(input.total_bytes()?.unwrap_or(0))
+    ///     .spawn_on(ThreadPool::default(), input.map_while(Result::ok));
+    /// while !job.is_finished() { println!("{}", job.progress()); thread::sleep(Duration::from_secs(1)); }
+    /// ```
+    pub fn spawn_on(&self, executor: impl Executor + Send + 'static, inputs: impl IntoIterator<Item = I> + Send + 'static) -> JobHandle<K, V, E> {
+        let state = Arc::new(self.job_state());
+        state.progress.reducers(self.reducers);
+        let (job, job_state) = (self.clone(), Arc::clone(&state));
+        let thread = thread::spawn(move || {
+            let (partitions, failures, spilled_runs) = job.run_job(&executor, inputs, &job_state)?;
+            Ok(JobReport { output: partitions.into_iter().flatten().collect(), failures, spilled_runs })
+        });
+        JobHandle::new(state, self.expected_bytes, thread)
+    }
+
+    /// [`MapReduce::try_run_on`] on tokio's blocking pool.
+    pub async fn try_run_async(&self, inputs: impl IntoIterator<Item = I>) -> Result<JobReport<K, V, E>, JobError<E>> {
+        let (partitions, failures, spilled_runs) = self.run_job_async(inputs).await?;
+        Ok(JobReport { output: partitions.into_iter().flatten().collect(), failures, spilled_runs })
+    }
+
+    async fn run_job_async(&self, inputs: impl IntoIterator<Item = I>) -> JobOutput<K, V, E> {
+
+        let state = Arc::new(self.job_state());
+        let mut failures = Vec::new();
+        state.progress.reducers(self.reducers);
+        let (mut inputs, mut next_segment) = (inputs.into_iter(), 0);
+        let (mut partitions, mut runs) = (self.partitions(), self.runs());
+        loop {
+            self.check_cancelled(&state, &mut failures, Phase::Map, next_segment)?;
+            let segments = self.read_segments(&mut inputs, &mut next_segment, &state);
+            if segments.is_empty() {
+                break;
+            }
+            let mut mapped = Vec::with_capacity(segments.len());
+            let batch_size = self.ordered.unwrap_or(segments.len());
+            for batch in batches(segments, batch_size) {
+                let map_tasks = batch.into_iter()
+                    .map(|segment| {
+                        let (job, state) = (self.clone(), Arc::clone(&state));
+                        tokio::task::spawn_blocking(move || job.map_segment(segment, &state))
+                    })
+                    .collect::<Vec<_>>();
+                for task in map_tasks {
+                    mapped.push(joined(task.await));
+                }
+            }
+            self.collect_mapped(&mut partitions, &mut failures, mapped, &state)?;
+            if self.over_spill_limit(&partitions) {
+                // the files are written on the blocking pool as well, the partitions go there and back
+                let job = self.clone();
+                let (spilled, result) = joined(tokio::task::spawn_blocking(move || {
+                    let result = job.spill(&mut partitions, &mut runs);
+                    ((partitions, runs), result)
+                }).await);
+                (partitions, runs) = spilled;
+                result.map_err(|failure| job_failed(&mut failures, failure))?;
+            }
+        }
+
+        self.check_cancelled(&state, &mut failures, Phase::Reduce, 0)?;
+        let spilled_runs = runs.iter().map(Vec::len).sum();
+        let reduce_tasks = partitions.into_iter().zip(runs).enumerate()
+            .map(|(reducer, (partition, runs))| {
+                let (job, state) = (self.clone(), Arc::clone(&state));
+                tokio::task::spawn_blocking(move || job.reduce_partition(reducer, partition, runs, &state))
+            })
+            .collect::<Vec<_>>();
+        let mut reduced = Vec::with_capacity(reduce_tasks.len());
+        for task in reduce_tasks {
+            reduced.push(joined(task.await));
+        }
+        Self::collect_reduced(failures, reduced, spilled_runs)
+    }
+}
+
+/// `items` cut into batches of `size`, the last one may be smaller.
+fn batches<T>(items: Vec<T>, size: usize) -> Vec<Vec<T>> {
+    let mut items = items.into_iter().peekable();
+    let mut batches = Vec::new();
+    while items.peek().is_some() {
+        batches.push(items.by_ref().take(size.max(1)).collect());
+    }
+    batches
+}
+
+/// The failures so far and the one the job fails on.
+fn job_failed<E>(failures: &mut Vec<SegmentFailure<E>>, failure: SegmentFailure<E>) -> JobError<E> {
+    failures.push(failure);
+    JobError { failures: std::mem::take(failures) }
+}
+
+fn joined<T>(result: Result<T, tokio::task::JoinError>) -> T {
+    result.unwrap_or_else(|error| panic::resume_unwind(error.into_panic()))
+}
+
+/// ## The example job: the sum of all digits in `DATA`.
+/// Every whitespace separated chunk is a record, mapped to the sum of its digits under the one and only key `()`.
+/// A chunk with anything but digits in it fails with [`NotADigit`].
+/// Its `u32` sums wrap past 4 billion in a release build, see [`MapReduce::try_sum`] for sums which can't.
+pub fn digit_sum<R: AsRef<str> + Send>() -> MapReduce<R, (), u32, NotADigit> {
+    MapReduce::try_new(
+        |data_segment: R, out| {
+            out.emit((), segment_sum(data_segment.as_ref())?);
+            Ok(())
+        },
+        |_, sums| Ok(sums.into_iter().sum()),
+    )
+    .combine(|_, sums| sums.into_iter().sum())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotADigit(pub char);
+
+impl fmt::Display for NotADigit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is not a digit", self.0)
+    }
+}
+
+impl Error for NotADigit {}
 
-    // explicitly given `sum` function type of `u32`
-    let final_result = children.iter().sum::<u32>();
-    println!("Final sum result: {}", final_result);
+fn segment_sum(data_segment: &str) -> Result<u32, NotADigit> {
+    data_segment
+        // iterate over the characters of our segment..
+        .chars()
+        // .. convert text-characters to their number value..
+        .map(|c| c.to_digit(10).ok_or(NotADigit(c)))
+        // .. and sum the resulting iterator of numbers, or stop at the first character which isn't one
+        .sum()
+}
+
+fn print_segments() {
+    for (i, data_segment) in DATA.split_whitespace().enumerate() {
+        println!("data segment {} is \"{}\"", i, data_segment);
+    }
+}
+
+fn print_result(result: Result<JobReport<(), u32, NotADigit>, JobError<NotADigit>>) {
+    match result {
+        Ok(report) => println!("Final sum result: {}", report.output[&()]),
+        Err(error) => println!("{}", error),
+    }
+}
+
+/// For data the size of `DATA` the sync version is faster by far, see the `digit_sum_bench` test.
+pub fn map_reduce_sync() {
+    print_segments();
+    print_result(digit_sum().try_run(DATA.split_whitespace()));
 }
 
 
-/// for smaller data (7rows <=) sync version is faster !!
 /// ## This is our data to process.
 ///     - We will calculate the sum of all digits via a threaded map-reduce algorithm.
-///     - Each whitespace separated chunk will be handled in a different thread.
+///     - Each whitespace separated chunk is a task on a [`ThreadPool`], sized to the number of cores.
+///
+/// Whether the pool ever beats the sync version depends on the number of cores, see the `digit_sum_bench` test.
 pub fn map_reduce_async() {
+    print_segments();
+    print_result(digit_sum().try_run_on(&ThreadPool::default(), DATA.split_whitespace()));
+}
 
-    // let mut counter = 0;
-    // ticker(move || {
-    //     println!("Ticker executing the closure of the main function. Counter: {}", counter);
-    //     counter += 1;
-    // });
-
-    // Make a vector to hold the child-threads which we will spawn.
-    let mut children = vec![];
-
-    /*************************************************************************
-     * "Map" phase
-     *
-     * Divide our data into segments, and apply initial processing
-     ************************************************************************/
-
-    // split our data into segments for individual calculation
-    // each chunk will be a reference (&str) into the actual data
-    let chunked_data = DATA.split_whitespace();
-
-    // Iterate over the data segments.
-    // .enumerate() adds the current loop index to whatever is iterated
-    // the resulting tuple "(index, element)" is then immediately
-    // "destructured" into two variables, "i" and "data_segment" with a
-    // "destructuring assignment"
-    for (i, data_segment) in chunked_data.enumerate() {
-        println!("data segment {} is \"{}\"", i, data_segment);
+/// The words of a text: lowercase, split at everything that isn't a letter, digit or apostrophe.
+pub(crate) fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// ## Word count: how often every word occurs, a line of text per record.
+pub fn word_count<R: AsRef<str> + Send>() -> MapReduce<R, String, u64> {
+    MapReduce::fold(|line: R, out| words(line.as_ref()).for_each(|word| out.emit(word, 1)), |a, b| a + b)
+}
+
+/// ## Inverted index: the documents every word occurs in, a `(document name, text)` per record.
+pub fn inverted_index<'a>() -> MapReduce<(&'a str, &'a str), String, BTreeSet<&'a str>> {
+    MapReduce::fold(
+        |(document, text): (&'a str, &'a str), out| words(text).for_each(|word| out.emit(word, BTreeSet::from([document]))),
+        |mut a, b| {
+            a.extend(b);
+            a
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::hash_map::RandomState, hash::BuildHasher, sync::Mutex};
+    use crate::{executor::StaticSplitExecutor, work_stealing::WorkStealingExecutor};
+    use super::*;
+
+    /// `DATA` with three typos, in chunks of 5 digits: 42 records, 14 segments of 3.
+    fn chunks(data: &str) -> Vec<&str> {
+        data.split_whitespace().flat_map(|row| row.as_bytes().chunks(5)).map(|chunk| std::str::from_utf8(chunk).expect("ascii")).collect()
+    }
+
+    /// A digit sum whose records sleep a random bit, so tasks finish in a different order on every run,
+    /// and which gives up on the third typo. What a run looks like from the outside: its events, and its output or error.
+    fn jittered_run(executor: &impl Executor, records: &[&str], ordered: bool) -> (Vec<JobEvent>, String) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let job = MapReduce::try_new(
+            |chunk: &str, out| -> Result<(), NotADigit> {
+                thread::sleep(Duration::from_micros(RandomState::new().hash_one(chunk) % 300));
+                out.emit((), segment_sum(chunk)?);
+                Ok(())
+            },
+            |_, sums| Ok(sums.into_iter().sum::<u32>()),
+        )
+        .segment_size(3)
+        .skip_bad_records(2)
+        .on_event(move |event| sink.lock().unwrap().push(event));
+        let job = if ordered { job.ordered(4) } else { job };
+        let result = match job.try_run_on(executor, records.iter().copied()) {
+            Ok(report) => format!("sum {}", report.output[&()]),
+            Err(error) => error.to_string(),
+        };
+        drop(job);
+        let events = std::mem::take(&mut *events.lock().unwrap());
+        (events, result)
+    }
+
+    #[test]
+    fn ordered_runs_are_identical() {
+
+        let data = DATA.replace("6532", "65_32").replace("7050", "7o50").replace("2624", "26.4");
+        let records = chunks(&data);
+
+        let (events, result) = jittered_run(&SyncExecutor, &records, true);
+        let map_events = events.iter().filter_map(|event| match event {
+            JobEvent::Finished { phase: Phase::Map, index } => Some((*index, "finished")),
+            JobEvent::Failed { phase: Phase::Map, index, .. } => Some((*index, "failed")),
+            _ => None,
+        });
+        // segments 1 and 4 skip a record and finish, 11 fails the job and the ones after it never report
+        let expected = (0..12).flat_map(|index| match index {
+            1 | 4 => vec![(index, "failed"), (index, "finished")],
+            11 => vec![(index, "failed")],
+            _ => vec![(index, "finished")],
+        });
+        assert!(map_events.eq(expected));
+        assert_eq!(result, [
+            "map-reduce job failed",
+            "  segment 1, record 5 (attempt 1): 'o' is not a digit",
+            "  segment 4, record 12 (attempt 1): '.' is not a digit",
+            "  segment 11, record 34 (attempt 1): '_' is not a digit",
+        ].join("\n"));
+
+        let first = (events, result);
+        let check = |name: &str, executor: &dyn Fn() -> (Vec<JobEvent>, String)| {
+            for _ in 0..30 {
+                assert_eq!(executor(), first, "{}", name);
+            }
+        };
+        check("pool", &|| jittered_run(&ThreadPool::new(4), &records, true));
+        check("work stealing", &|| jittered_run(&WorkStealingExecutor::new(4), &records, true));
+        check("static split", &|| jittered_run(&StaticSplitExecutor::new(4), &records, true));
+        check("threads", &|| jittered_run(&ThreadExecutor::new(4), &records, true));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ordered_events_on_tokio() {
+        let data = DATA.replace("6532", "65_32");
+        for _ in 0..10 {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let sink = Arc::clone(&events);
+            let job = digit_sum().skip_bad_records(1).ordered(2).on_event(move |event| sink.lock().unwrap().push(event));
+            let report = job.try_run_async(data.split_whitespace().map(str::to_string).collect::<Vec<_>>()).await.unwrap();
+            assert_eq!((report.output[&()], report.failures.len()), (1012 - 165, 1));
+            let finished = events.lock().unwrap().iter().filter_map(|event| match event {
+                JobEvent::Finished { phase: Phase::Map, index } => Some(*index),
+                _ => None,
+            }).collect::<Vec<_>>();
+            assert_eq!(finished, (0..6).collect::<Vec<_>>());
+        }
+    }
+
+    /// The same job on all three kinds of executor, they have to agree.
+    #[tokio::test]
+    async fn every_executor_agrees() {
+        let sync = digit_sum().try_run(DATA.split_whitespace()).unwrap().output;
+        let threads = digit_sum().segment_size(2).try_run_on(&ThreadExecutor::default(), DATA.split_whitespace()).unwrap().output;
+        let tokio = digit_sum().try_run_async(DATA.split_whitespace()).await.unwrap().output;
+        assert_eq!(sync, threads);
+        assert_eq!(threads, tokio);
+        assert_eq!(sync[&()], 1012);
+    }
+
+    /// ## Where threads start to pay off for the digit sum.
+    /// Rows like the ones of `DATA` (35 digits each), summed by:
+    /// - `sync`: the calling thread,
+    /// - `sync/64`: the same with segments of 64 rows, the engine's overhead per segment is most of the cost,
+    /// - `per chunk`: a thread per row, what `map_reduce_async` used to do (left out past 1,000 rows),
+    /// - `pool`: a task per row, each a job on the workers of one [`ThreadPool`] shared by every run,
+    /// - `pool/64`: the same with segments of 64 rows, a job per segment, it's what competes with `sync/64`.
+    ///
+    /// Each size runs until about 100ms passed and the median is printed, with the first size at which a pool beat `sync`.
+    /// Summing 35 digits takes well under a microsecond and a thread per row costs ~15-60µs a row, depending on the machine,
+    /// so the ">= 7rows" guess was off by a lot. On a single core handing a job to a worker costs ~0.5µs, a batch ~7µs,
+    /// the pool stays ~2x behind `sync` and `pool/64` ~15% behind `sync/64`, it takes more cores for the pool to actually win.
+    #[test]
+    #[ignore = "benchmark, takes a few seconds: cargo test --release digit_sum_bench -- --ignored --nocapture"]
+    fn digit_sum_bench() {
+
+        let pool = ThreadPool::default();
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next_digit = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            char::from(b'0' + (seed % 10) as u8)
+        };
+        let rows = (0..10_000).map(|_| (0..35).map(|_| next_digit()).collect::<String>()).collect::<Vec<_>>();
+
+        let median = |run: &dyn Fn() -> Result<JobReport<(), u32, NotADigit>, JobError<NotADigit>>, expected: u32| {
+            let mut times = Vec::new();
+            let started = Instant::now();
+            while started.elapsed() < Duration::from_millis(100) || times.len() < 3 {
+                let run_started = Instant::now();
+                assert_eq!(run().expect("digits only").output[&()], expected);
+                times.push(run_started.elapsed());
+            }
+            times.sort();
+            times[times.len() / 2]
+        };
+
+        println!("{:>7} {:>11} {:>11} {:>11} {:>11} {:>11}", "rows", "sync", "sync/64", "per chunk", "pool", "pool/64");
+        let mut crossover = None;
+        for count in [1, 2, 4, 7, 8, 16, 64, 256, 1_024, 4_096, 10_000] {
+            let rows = &rows[..count];
+            let expected = rows.iter().map(|row| segment_sum(row)).sum::<Result<_, _>>().expect("digits only");
+            let records = || rows.iter().map(String::as_str);
+
+            let sync = median(&|| digit_sum().try_run(records()), expected);
+            let sync_segmented = median(&|| digit_sum().segment_size(64).try_run(records()), expected);
+            let per_chunk = (count <= 1_000).then(|| median(&|| digit_sum().try_run_on(&ThreadExecutor::new(count), records()), expected));
+            let pooled = median(&|| digit_sum().try_run_on(&pool, records()), expected);
+            let segmented = median(&|| digit_sum().segment_size(64).try_run_on(&pool, records()), expected);
+
+            if pooled < sync || segmented < sync_segmented {
+                crossover.get_or_insert(count);
+            }
+            let per_chunk = per_chunk.map_or("-".to_string(), |time| format!("{:.2?}", time));
+            println!("{:>7} {:>11.2?} {:>11.2?} {:>11} {:>11.2?} {:>11.2?}", count, sync, sync_segmented, per_chunk, pooled, segmented);
+        }
+        match crossover {
+            Some(rows) => println!("the pool ({} threads) first beat sync at {} rows", pool.threads(), rows),
+            None => println!("the pool ({} threads) never beat sync", pool.threads()),
+        }
+    }
+
+    /// Word count with its keys hashed and with them split into ranges, three reducers each, then an inverted index.
+    #[test]
+    fn partitioned_word_counts_and_an_inverted_index() {
+
+        const TEXT: &str = "Each whitespace separated chunk will be handled in a different thread.
+            The map phase divides our data into segments, the reduce phase combines them.
+            Each segment is mapped, each key is reduced, and each reducer gets its keys.";
+
+        let hashed = word_count().reducers(3).run_partitioned_on(&ThreadExecutor::default(), TEXT.lines());
+
+        // the ranges are sorted one after the other, so are the reducers' outputs
+        let ranged = word_count()
+            .reducers(3)
+            .partitioner(RangePartitioner::new(vec!["h".to_string(), "r".to_string()]))
+            .run_partitioned_on(&ThreadExecutor::default(), TEXT.lines());
+        assert!(ranged.iter().flat_map(|counts| counts.keys()).is_sorted());
+        assert_eq!(ranged.into_iter().flatten().collect::<BTreeMap<_, _>>(), hashed.into_iter().flatten().collect());
+
+        let documents = [
+            ("map_reduce.rs", "Divide our data into segments, and apply initial processing"),
+            ("ticker.rs", "Runs the ticker loop on a dedicated std thread"),
+            ("thread_pool.rs", "A fixed number of worker threads sharing one job queue, a thread per job no more"),
+        ];
+        let index = inverted_index().run(documents);
+        assert_eq!(index["thread"], BTreeSet::from(["thread_pool.rs", "ticker.rs"]));
+    }
+
+
+    /// Word count and digit sum over files instead of constants: a directory of text files split by lines and by
+    /// 64 byte ranges (the same counts either way), then `DATA` written to a file with a custom splitter.
+    /// `Input::stdin()` works the same, e.g. `cat *.txt | lrn-rs`.
+    #[test]
+    fn word_count_and_digit_sum_over_files() -> io::Result<()> {
+
+        let directory = std::env::temp_dir().join(format!("lrn-rs-input-{}", std::process::id()));
+        fs::create_dir_all(directory.join("more"))?;
+        fs::write(directory.join("a.txt"), "the map phase divides our data into segments\nthe reduce phase combines them\n")?;
+        fs::write(directory.join("b.txt"), "each segment is mapped\r\neach key is reduced")?;
+        fs::write(directory.join("more").join("c.txt"), "a different thread for each chunk\n".repeat(100))?;
 
-        // Process each data segment in a separate thread
-        //
-        // spawn() returns a handle to the new thread,
-        // which we MUST keep to access the returned value
-        //
-        // 'move || -> u32' is syntax for a closure that:
-        // * takes no arguments ('||')
-        // * takes ownership of its captured variables ('move') and
-        // * returns an unsigned 32-bit integer ('-> u32')
-        //
-        // Rust is smart enough to infer the '-> u32' from
-        // the closure itself so we could have left that out.
-        //
-        // TODO: try removing the 'move' and see what happens
-        children.push(thread::spawn(move || -> u32 {
-            // Calculate the intermediate sum of this segment:
-            let result = data_segment
-                        // iterate over the characters of our segment..
-                        .chars()
-                        // .. convert text-characters to their number value..
-                        .map(|c| c.to_digit(10).expect("should be a digit"))
-                        // .. and sum the resulting iterator of numbers
-                        .sum();
-
-            // println! locks stdout, so no text-interleaving occurs
-            println!("processed segment {}, result={}", i, result);
-
-            // "return" not needed, because Rust is an "expression language", the
-            // last evaluated expression in each block is automatically its value.
-            result
-
-        }));
-    }
-
-
-    /*************************************************************************
-     * "Reduce" phase
-     *
-     * Collect our intermediate results, and combine them into a final result
-     ************************************************************************/
-
-    // combine each thread's intermediate results into a single final sum.
-    //
-    // we use the "turbofish" ::<> to provide sum() with a type hint.
-    //
-    // TODO: try without the turbofish, by instead explicitly
-    // specifying the type of final_result
-    let final_result = children.into_iter().map(|c| c.join().unwrap()).sum::<u32>();
-
-    println!("Final sum result: {}", final_result);
-
-
-}
\ No newline at end of file
+        // small segments and read-ahead, at most 2 x 4 lines of input are in memory at any time
+        let by_lines = word_count()
+            .segment_size(4)
+            .read_ahead(2)
+            .run_input_on(&ThreadPool::default(), Input::dir(&directory)?)?;
+        let by_ranges = word_count().run_input_on(&ThreadPool::default(), Input::dir(&directory)?.split(ByteRanges::new(64)))?;
+        assert_eq!(by_lines, by_ranges);
+        assert_eq!((by_lines["each"], by_lines["reduced"]), (102, 1));
+
+        // a record per whitespace separated chunk, like `DATA.split_whitespace()`
+        let chunks = |reader: &mut dyn io::BufRead| loop {
+            match Lines.next_record(reader)? {
+                Some(line) if line.trim().is_empty() => continue,
+                line => return Ok(line.map(|line| line.trim().to_string())),
+            }
+        };
+        let file = directory.join("data.txt");
+        fs::write(&file, DATA)?;
+        let records = Input::file(&file).split(chunks).collect::<io::Result<Vec<_>>>()?;
+        let sum = digit_sum().try_run(records).map_err(io::Error::other)?.output[&()];
+        assert_eq!(sum, 1012);
+
+        // a missing file is an error, not an empty input
+        assert!(word_count::<String>().run_input_on(&SyncExecutor, Input::file(directory.join("missing.txt"))).is_err());
+
+        fs::remove_dir_all(&directory)
+    }
+
+
+    /// `DATA` with typos, under each of the error policies.
+    #[test]
+    fn error_policies() {
+
+        let data = DATA.replace("7050", "7o50").replace("2624", "26.4").replace("6532", "65_32");
+        let records = || data.split_whitespace();
+
+        // fail fast: the first typo ends the job
+        let error = digit_sum().try_run(records()).unwrap_err();
+        assert_eq!((error.failures.len(), error.failures[0].index), (1, 0));
+
+        // skip: the rows with a typo are left out, as long as there are no more than 3 of them
+        let report = digit_sum().skip_bad_records(3).try_run(records()).unwrap();
+        assert_eq!((report.output[&()], report.failures.len()), (1012 - 187 - 157 - 165, 3));
+        assert!(digit_sum().skip_bad_records(2).try_run(records()).is_err());
+
+        // retry: a lookup which fails with an error, then with a panic, then works, the first segment gets through on its third attempt
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = Arc::clone(&calls);
+        let flaky = MapReduce::try_new(
+            move |row: &str, out| {
+                match calls_clone.fetch_add(1, Ordering::SeqCst) {
+                    0 => return Err("service unavailable".to_string()),
+                    1 => panic!("connection reset"),
+                    _ => {}
+                }
+                out.emit((), row.len());
+                Ok(())
+            },
+            |_, lengths| Ok(lengths.into_iter().sum::<usize>()),
+        ).segment_size(2).retry_segments(3);
+        let report = flaky.try_run(DATA.split_whitespace()).unwrap();
+        assert_eq!((report.output[&()], report.failures.len()), (6 * 35, 2));
+    }
+
+
+    /// Word count over 20,000 generated lines of 10,000 different words, with at most 5,000 values in memory
+    /// before spilling, against the same job all in memory. Runs get written, and none are left behind.
+    #[test]
+    fn spilled_word_count_matches_in_memory() -> io::Result<()> {
+
+        // the same pseudo random words on every run
+        let mut seed = 42_u64;
+        let lines = (0..20_000)
+            .map(|_| (0..8).map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                format!("w{}", (seed >> 33) % 10_000)
+            }).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+
+        let directory = env::temp_dir().join(format!("lrn-rs-spill-{}", std::process::id()));
+        fs::create_dir_all(&directory)?;
+
+        let in_memory = word_count().segment_size(100).run_on(&ThreadPool::default(), &lines);
+        let spilled = word_count()
+            .segment_size(100)
+            .read_ahead(8)
+            .spill_to(&directory, 5_000)
+            .try_run_on(&ThreadPool::default(), &lines)
+            .map_err(io::Error::other)?;
+        assert!(spilled.spilled_runs > 0);
+        assert_eq!(spilled.output, in_memory);
+        assert_eq!(spilled.output.values().sum::<u64>(), 8 * 20_000);
+        assert_eq!(fs::read_dir(&directory)?.count(), 0, "every run is deleted once merged");
+
+        fs::remove_dir(&directory)
+    }
+
+
+    /// A word count slowed down to 2ms a line, once watching its progress while it runs, once cancelled after its
+    /// first 10 segments, watching the events through a channel.
+    #[test]
+    fn progress_and_cancellation() -> io::Result<()> {
+
+        let file = env::temp_dir().join(format!("lrn-rs-progress-{}.txt", std::process::id()));
+        fs::write(&file, (0..500).map(|line| format!("line {} of the map phase\n", line % 10)).collect::<String>())?;
+        let slow_word_count = || {
+            MapReduce::fold(
+                |line: String, out| {
+                    thread::sleep(Duration::from_millis(2));
+                    words(&line).for_each(|word| out.emit(word, 1));
+                },
+                |a, b| a + b,
+            )
+            .segment_size(10)
+            .read_ahead(8)
+            // `Lines` drops the newline
+            .record_bytes(|line: &String| line.len() + 1)
+        };
+
+        let input = Input::file(&file);
+        let job = slow_word_count()
+            .expected_bytes(input.total_bytes()?.unwrap_or(0))
+            .spawn_on(ThreadPool::default(), input.map_while(Result::ok));
+        let mut done = 0;
+        while !job.is_finished() {
+            let progress = job.progress();
+            assert!(progress.segments_done >= done && progress.bytes <= progress.bytes_total.unwrap_or(u64::MAX));
+            done = progress.segments_done;
+            thread::sleep(Duration::from_millis(20));
+        }
+        let progress = job.progress();
+        let report = job.join().map_err(io::Error::other)?;
+        assert_eq!((progress.segments_done, progress.segments_total, progress.reducers_done), (50, Some(50), progress.reducers));
+        assert_eq!(Some(progress.bytes), progress.bytes_total);
+        assert_eq!(report.output["line"], 500);
+
+        let (sender, events) = std::sync::mpsc::channel();
+        let job = slow_word_count()
+            .on_event(move |event| {
+                let _ = sender.send(event);
+            })
+            .spawn_on(ThreadPool::default(), Input::file(&file).map_while(Result::ok));
+        let mut finished = 0;
+        for event in events.iter() {
+            if let JobEvent::Finished { phase: Phase::Map, .. } = event {
+                finished += 1;
+                if finished == 10 {
+                    job.cancel();
+                }
+            }
+        }
+        // the channel ends with the job, which drops its callback
+        let progress = job.progress();
+        let error = job.join().unwrap_err();
+        assert!(!error.failures.is_empty());
+        assert!(error.failures.iter().all(|failure| matches!(failure.error, TaskError::Cancelled)));
+        assert!(progress.segments_done < 50 && progress.reducers_done == 0);
+
+        fs::remove_file(&file)
+    }
+
+
+    /// ## Summing the digits of `DATA` (1012 in all) into a `u8`, in every [`Arithmetic`], and into bigger types.
+    /// Then the product of its non-zero digits, about 300 bits: `u128` overflows, a [`BigUint`] gets it exactly,
+    /// checked against the product of the row products, which do fit into a `u128`.
+    #[test]
+    fn overflowing_sums_and_products() {
+
+        fn digits<V: From<u8>>(row: &str, out: &mut Emitter<(), V>) -> Result<(), Overflowed> {
+            row.bytes().filter(u8::is_ascii_digit).for_each(|digit| out.emit((), V::from(digit - b'0')));
+            Ok(())
+        }
+        fn non_zero_digits<V: From<u8>>(row: &str, out: &mut Emitter<(), V>) -> Result<(), Overflowed> {
+            row.bytes().filter(|digit| (b'1'..=b'9').contains(digit)).for_each(|digit| out.emit((), V::from(digit - b'0')));
+            Ok(())
+        }
+        let pool = ThreadPool::default();
+        let records = || DATA.split_whitespace();
+
+        for arithmetic in [Arithmetic::Wrapping, Arithmetic::Saturating, Arithmetic::Checked] {
+            let sum = MapReduce::<_, _, u8, _>::try_sum(digits, arithmetic).segment_size(2).try_run_on(&pool, records());
+            match arithmetic {
+                Arithmetic::Wrapping => assert_eq!(sum.unwrap().output[&()], (1012 % 256) as u8),
+                Arithmetic::Saturating => assert_eq!(sum.unwrap().output[&()], u8::MAX),
+                Arithmetic::Checked => assert!(matches!(sum.unwrap_err().failures[0].error, TaskError::Failed(Overflowed))),
+            }
+        }
+        let sum = MapReduce::<_, _, u32, _>::try_sum(digits, Arithmetic::Checked).try_run_on(&pool, records()).unwrap();
+        let big_sum = MapReduce::<_, _, BigUint, _>::try_sum(digits, Arithmetic::Checked).try_run_on(&pool, records()).unwrap();
+        assert_eq!((sum.output[&()], big_sum.output[&()].to_string()), (1012, "1012".to_string()));
+
+        let product = MapReduce::<_, _, u128, _>::try_product(non_zero_digits, Arithmetic::Checked).try_run_on(&pool, records());
+        let big_product = MapReduce::<_, _, BigUint, _>::try_product(non_zero_digits, Arithmetic::Checked)
+            .segment_size(2)
+            .try_run_on(&pool, records())
+            .unwrap()
+            .output[&()]
+            .clone();
+        assert!(matches!(product.unwrap_err().failures[0].error, TaskError::Failed(Overflowed)));
+
+        let row_products = records()
+            .map(|row| row.bytes().filter(|digit| *digit != b'0').map(|digit| (digit - b'0') as u128).product::<u128>())
+            .map(BigUint::from)
+            .product::<BigUint>();
+        assert_eq!(big_product, row_products);
+        assert_eq!(big_product.to_string().parse::<BigUint>(), Ok(big_product));
+    }
+
+    /// A checked sum over a segment of 10,000 digits keeps their sum, not the digits, and the shuffle keeps one
+    /// value per key of however many segments. A segment whose own sum doesn't fit fails as a whole.
+    #[test]
+    fn a_checked_sum_keeps_one_value_per_segment() {
+        fn digits<V: From<u8>>(row: &str, out: &mut Emitter<(), V>) -> Result<(), Overflowed> {
+            row.bytes().for_each(|digit| out.emit((), V::from(digit - b'0')));
+            Ok(())
+        }
+        let rows = vec!["9".repeat(100); 100];
+        let job = MapReduce::<_, _, u64, _>::try_sum(digits, Arithmetic::Checked).segment_size(100).reducers(2);
+
+        let mapped = (0..3)
+            .map(|index| {
+                let segment = Segment { index, offset: index * 100, records: rows.iter().map(String::as_str).collect() };
+                job.map_segment(segment, &JobState::default())
+            })
+            .map(|output| {
+                assert!(!output.failed && output.failures.is_empty());
+                assert_eq!(output.grouped[&()], [9 * 100 * 100]);
+                output.grouped
+            })
+            .collect();
+        let mut partitions = job.partitions();
+        job.shuffle(&mut partitions, mapped).unwrap();
+        assert_eq!(partitions.iter().flat_map(|partition| partition.values()).collect::<Vec<_>>(), [&[3 * 9 * 100 * 100]]);
+
+        let error = MapReduce::<_, _, u16, _>::try_sum(digits, Arithmetic::Checked)
+            .segment_size(100)
+            .try_run(rows.iter().map(String::as_str))
+            .unwrap_err();
+        let failure = &error.failures[0];
+        assert_eq!((failure.phase, failure.index, failure.record), (Phase::Map, 0, None));
+        assert!(matches!(failure.error, TaskError::Failed(Overflowed)));
+    }
+}
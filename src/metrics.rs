@@ -0,0 +1,163 @@
+use std::{fmt::Write, time::Duration};
+
+/// ## A fixed bucket histogram of durations.
+/// Bucket `i` counts the samples `<= bounds[i]`, the last bucket everything above the largest bound.
+/// Good enough to see where tick jitter and closure run times land, without keeping every sample around.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: Vec<Duration>,
+    counts: Vec<u64>,
+    sum: Duration,
+    max: Duration,
+}
+
+impl Default for Histogram {
+    /// Buckets from 1ms to 10s, roughly in 1-2.5-5 steps.
+    fn default() -> Self {
+        Self::with_bounds(
+            [1, 2, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000].map(Duration::from_millis).to_vec()
+        )
+    }
+}
+
+impl Histogram {
+
+    /// `bounds` get sorted, duplicates removed.
+    pub fn with_bounds(mut bounds: Vec<Duration>) -> Self {
+        bounds.sort();
+        bounds.dedup();
+        Histogram { counts: vec![0; bounds.len() + 1], bounds, sum: Duration::ZERO, max: Duration::ZERO }
+    }
+
+    pub fn record(&mut self, sample: Duration) {
+        let bucket = self.bounds.partition_point(|bound| *bound < sample);
+        self.counts[bucket] += 1;
+        self.sum += sample;
+        self.max = self.max.max(sample);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            // in nanoseconds, a `u32` divisor would wrap past 4 billion samples
+            count => {
+                let mean = self.sum.as_nanos() / count as u128;
+                Duration::new((mean / 1_000_000_000) as u64, (mean % 1_000_000_000) as u32)
+            }
+        }
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile (`0.0..=1.0`), the max sample for the overflow bucket.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let rank = (q.clamp(0.0, 1.0) * self.count() as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return self.bounds.get(bucket).copied().unwrap_or(self.max).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// `(upper bound, cumulative count)` pairs, `None` standing for `+Inf`.
+    pub fn buckets(&self) -> Vec<(Option<Duration>, u64)> {
+        let mut cumulative = 0;
+        self.counts.iter().enumerate().map(|(bucket, count)| {
+            cumulative += count;
+            (self.bounds.get(bucket).copied(), cumulative)
+        }).collect()
+    }
+
+    fn write_prometheus(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, cumulative) in self.buckets() {
+            let le = bound.map_or("+Inf".to_string(), |bound| bound.as_secs_f64().to_string());
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum.as_secs_f64());
+        let _ = writeln!(out, "{}_count {}", name, self.count());
+    }
+}
+
+
+/// ## What a ticker has been up to, see [`TickerHandle::metrics`](crate::ticker::TickerHandle::metrics).
+/// - `jitter`: how late each tick started compared to when it was due,
+/// - `execution`: how long the closure took,
+/// - `overruns`: ticks whose closure took longer than the period (the time from its due time to the next one),
+/// - `skipped`: due ticks which never ran because of [`MissedTickBehavior::Skip`](crate::ticker::MissedTickBehavior)
+///   or `Delay` (a `Burst` ticker runs them all, late).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TickerMetrics {
+    pub ticks: u64,
+    pub overruns: u64,
+    pub skipped: u64,
+    /// Panics caught by a supervised ticker.
+    pub failures: u64,
+    pub jitter: Histogram,
+    pub execution: Histogram,
+}
+
+impl TickerMetrics {
+
+    /// Prometheus text exposition format, every metric prefixed with `name` (e.g. `nightly_job`).
+    pub fn to_prometheus(&self, name: &str) -> String {
+
+        let mut out = String::new();
+
+        let counters = [
+            ("ticks_total", "Ticks run.", self.ticks),
+            ("overruns_total", "Ticks whose closure took longer than the period.", self.overruns),
+            ("skipped_ticks_total", "Due ticks which were never run.", self.skipped),
+            ("failures_total", "Panics caught by the supervisor.", self.failures),
+        ];
+        for (metric, help, value) in counters {
+            let _ = writeln!(out, "# HELP {}_{} {}", name, metric, help);
+            let _ = writeln!(out, "# TYPE {}_{} counter", name, metric);
+            let _ = writeln!(out, "{}_{} {}", name, metric, value);
+        }
+
+        self.jitter.write_prometheus(&mut out, &format!("{}_tick_jitter_seconds", name), "How late ticks started.");
+        self.execution.write_prometheus(&mut out, &format!("{}_tick_duration_seconds", name), "How long the closure ran.");
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_of_a_few_samples() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.mean(), Duration::ZERO);
+        [1, 2, 6].map(Duration::from_millis).into_iter().for_each(|sample| histogram.record(sample));
+        assert_eq!(histogram.mean(), Duration::from_millis(3));
+    }
+
+    #[test]
+    fn mean_of_more_samples_than_a_u32_counts() {
+        // 2^32 samples of a second each, a `u32` count would be 0
+        let histogram = Histogram {
+            bounds: vec![Duration::from_secs(1)],
+            counts: vec![1 << 32, 0],
+            sum: Duration::from_secs(1 << 32),
+            max: Duration::from_secs(1),
+        };
+        assert_eq!(histogram.mean(), Duration::from_secs(1));
+    }
+}
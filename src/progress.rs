@@ -0,0 +1,214 @@
+use std::{collections::HashMap, fmt, sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc, Mutex}, thread, time::{Duration, Instant}};
+use crate::{map_reduce::{JobError, JobReport, JobState, Phase}, shutdown::CancellationToken};
+
+/// ### What a job reports through [`MapReduce::on_event`](crate::map_reduce::MapReduce::on_event), as it happens.
+/// - `Started` / `Finished`: a map segment or a reducer (`index`), a segment is started with its first record
+///   and finished with its last, however an executor splits it.
+/// - `Failed`: every failure which ends up in the job's report or error, `error` is its message.
+///
+/// Events come from the threads running the tasks, several at a time.
+/// For a channel: `.on_event(move |event| { let _ = sender.send(event); })`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobEvent {
+    Started { phase: Phase, index: usize },
+    Finished { phase: Phase, index: usize },
+    Failed { phase: Phase, index: usize, record: Option<usize>, attempt: u32, error: String },
+}
+
+/// ## How far a job got, see [`JobHandle::progress`].
+/// The number of segments is only known once the whole input was read, the number of bytes (of the records,
+/// see [`MapReduce::record_bytes`](crate::map_reduce::MapReduce::record_bytes)) only if the job was told
+/// with [`MapReduce::expected_bytes`](crate::map_reduce::MapReduce::expected_bytes). The ETA goes by whichever is known.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub segments_done: usize,
+    pub segments_failed: usize,
+    pub segments_total: Option<usize>,
+    pub bytes: u64,
+    pub bytes_total: Option<u64>,
+    pub reducers_done: usize,
+    pub reducers: usize,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+
+    /// Of the map phase, from 0 to 1.
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.segments_total, self.bytes_total) {
+            (Some(0), _) => Some(1.0),
+            (Some(total), _) => Some((self.segments_done + self.segments_failed) as f64 / total as f64),
+            (None, Some(total)) if total > 0 => Some((self.bytes as f64 / total as f64).min(1.0)),
+            _ => None,
+        }
+    }
+
+    /// Time left for the map phase if it goes on as fast as it went so far.
+    pub fn eta(&self) -> Option<Duration> {
+        self.fraction()
+            .filter(|fraction| *fraction > 0.0)
+            .map(|fraction| self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.segments_total {
+            Some(total) => write!(f, "{}/{} segments", self.segments_done, total)?,
+            None => write!(f, "{}/? segments", self.segments_done)?,
+        }
+        if self.segments_failed > 0 {
+            write!(f, " ({} failed)", self.segments_failed)?;
+        }
+        write!(f, ", {} bytes", self.bytes)?;
+        write!(f, ", {}/{} reducers, {:.1?}", self.reducers_done, self.reducers, self.elapsed)?;
+        match self.eta() {
+            Some(eta) => write!(f, ", ETA {:.1?}", eta),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Records of a segment still to map, a segment split by the executor is done with its last piece.
+struct InFlight {
+    records: usize,
+    started: bool,
+}
+
+/// The counters behind [`Progress`], updated by the tasks of one run.
+pub(crate) struct Counters {
+    started: Instant,
+    in_flight: Mutex<HashMap<usize, InFlight>>,
+    segments_done: AtomicUsize,
+    segments_failed: AtomicUsize,
+    /// `usize::MAX` until the input is read.
+    segments_total: AtomicUsize,
+    bytes: AtomicU64,
+    reducers_done: AtomicUsize,
+    reducers: AtomicUsize,
+}
+
+impl Default for Counters {
+    fn default() -> Self {
+        Counters {
+            started: Instant::now(),
+            in_flight: Mutex::new(HashMap::new()),
+            segments_done: AtomicUsize::new(0),
+            segments_failed: AtomicUsize::new(0),
+            segments_total: AtomicUsize::new(usize::MAX),
+            bytes: AtomicU64::new(0),
+            reducers_done: AtomicUsize::new(0),
+            reducers: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Counters {
+
+    fn in_flight(&self) -> std::sync::MutexGuard<'_, HashMap<usize, InFlight>> {
+        self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// A segment was read, with this many records.
+    pub(crate) fn read(&self, segment: usize, records: usize) {
+        self.in_flight().insert(segment, InFlight { records, started: false });
+    }
+
+    /// The input is exhausted after `segments`.
+    pub(crate) fn input_done(&self, segments: usize) {
+        self.segments_total.store(segments, Ordering::SeqCst);
+    }
+
+    /// Whether it's the segment's first piece.
+    pub(crate) fn start(&self, segment: usize) -> bool {
+        self.in_flight().get_mut(&segment).is_some_and(|in_flight| !std::mem::replace(&mut in_flight.started, true))
+    }
+
+    /// Whether it was the segment's last piece.
+    pub(crate) fn finish(&self, segment: usize, records: usize) -> bool {
+        let mut in_flight = self.in_flight();
+        let Some(segment_in_flight) = in_flight.get_mut(&segment) else {
+            return false;
+        };
+        segment_in_flight.records = segment_in_flight.records.saturating_sub(records);
+        if segment_in_flight.records > 0 {
+            return false;
+        }
+        in_flight.remove(&segment);
+        self.segments_done.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    /// Counted once, however many of its pieces fail.
+    pub(crate) fn fail(&self, segment: usize) {
+        if self.in_flight().remove(&segment).is_some() {
+            self.segments_failed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn add_bytes(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::SeqCst);
+    }
+
+    pub(crate) fn reducers(&self, reducers: usize) {
+        self.reducers.store(reducers, Ordering::SeqCst);
+    }
+
+    pub(crate) fn reducer_done(&self) {
+        self.reducers_done.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn snapshot(&self, bytes_total: Option<u64>) -> Progress {
+        let segments_total = self.segments_total.load(Ordering::SeqCst);
+        Progress {
+            segments_done: self.segments_done.load(Ordering::SeqCst),
+            segments_failed: self.segments_failed.load(Ordering::SeqCst),
+            segments_total: (segments_total != usize::MAX).then_some(segments_total),
+            bytes: self.bytes.load(Ordering::SeqCst),
+            bytes_total,
+            reducers_done: self.reducers_done.load(Ordering::SeqCst),
+            reducers: self.reducers.load(Ordering::SeqCst),
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+
+/// ## A job running on its own thread, see [`MapReduce::spawn_on`](crate::map_reduce::MapReduce::spawn_on).
+/// [`JobHandle::cancel`] stops it as soon as it can: segments and reducers which are running stop at their next
+/// record or key, the ones which haven't started don't, and no more input is read. The job then fails with
+/// a [`TaskError::Cancelled`](crate::map_reduce::TaskError::Cancelled) for every segment or reducer it stopped.
+pub struct JobHandle<K, V, E> {
+    state: Arc<JobState>,
+    bytes_total: Option<u64>,
+    thread: thread::JoinHandle<Result<JobReport<K, V, E>, JobError<E>>>,
+}
+
+impl<K, V, E> JobHandle<K, V, E> {
+
+    pub(crate) fn new(state: Arc<JobState>, bytes_total: Option<u64>, thread: thread::JoinHandle<Result<JobReport<K, V, E>, JobError<E>>>) -> Self {
+        JobHandle { state, bytes_total, thread }
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.state.progress.snapshot(self.bytes_total)
+    }
+
+    pub fn cancel(&self) {
+        self.state.cancel.cancel();
+    }
+
+    /// The job's token, to cancel it along with other things (e.g. registered with a `Shutdown`).
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.state.cancel.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the job, a panic on its thread is resumed here.
+    pub fn join(self) -> Result<JobReport<K, V, E>, JobError<E>> {
+        self.thread.join().unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+    }
+}
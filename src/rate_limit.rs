@@ -0,0 +1,570 @@
+use std::{collections::VecDeque, future::Future, pin::Pin, sync::{Arc, Mutex, MutexGuard}, thread, time::{Duration, Instant}};
+use tokio::sync::Notify;
+use crate::clock::{Clock, RealClock};
+
+/// ## Something handing out permits at a limited rate.
+/// [`TokenBucket`], [`LeakyBucket`] and [`SlidingWindow`] only differ in how they decide, callers go through this trait:
+/// - [`RateLimiter::try_acquire`] never waits, on `Err` it says how long until a permit might be free,
+/// - [`RateLimiter::acquire`] / [`RateLimiter::acquire_async`] sleep on the limiter's clock until they got one,
+/// - [`RateLimiter::run`] / [`RateLimiter::limit`] wrap a closure / a future in an `acquire`.
+///
+/// Every limiter takes a `.clock(...)`, with the [`ManualClock`] a ticker is tested on the limiter sees the same fake time.
+pub trait RateLimiter: Send + Sync {
+
+    fn try_acquire(&self) -> Result<(), Duration>;
+
+    /// The clock `acquire` sleeps on.
+    fn clock_ref(&self) -> &dyn Clock;
+
+    /// Blocks until a permit is free, forever if the limiter can never grant one (`Err(Duration::MAX)`).
+    fn acquire(&self) {
+        while let Err(wait) = self.try_acquire() {
+            let clock = self.clock_ref();
+            clock.sleep_until(clock.now().checked_add(wait), &|| false);
+        }
+    }
+
+    fn acquire_async(&self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(async move {
+            while let Err(wait) = self.try_acquire() {
+                let clock = self.clock_ref();
+                clock.sleep_until_async(clock.now().checked_add(wait)).await;
+            }
+        })
+    }
+
+    fn run<R>(&self, f: impl FnOnce() -> R) -> R where Self: Sized {
+        self.acquire();
+        f()
+    }
+
+    fn limit<F: Future>(&self, future: F) -> impl Future<Output = F::Output> where Self: Sized {
+        async move {
+            self.acquire_async().await;
+            future.await
+        }
+    }
+}
+
+
+/// ## Up to `capacity` permits at once, `refill` more every `interval`.
+/// Tokens come in whole batches on a fixed grid, like a ticker adding them: a full bucket allows a burst of `capacity`,
+/// after that callers get `refill` permits per `interval`.
+pub struct TokenBucket {
+    capacity: u64,
+    refill: u64,
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<TokenState>,
+}
+
+struct TokenState {
+    tokens: u64,
+    // start of the refill grid and number of refills already added, set on first use so `.clock()` can still change time
+    start: Option<Instant>,
+    refills: u64,
+}
+
+impl TokenBucket {
+
+    /// Starts full.
+    pub fn new(capacity: u64, refill: u64, interval: Duration) -> Self {
+        TokenBucket {
+            capacity,
+            refill,
+            interval: interval.max(Duration::from_nanos(1)),
+            clock: Arc::new(RealClock::default()),
+            state: Mutex::new(TokenState { tokens: capacity, start: None, refills: 0 }),
+        }
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Takes `n` tokens at once or none, `Err(Duration::MAX)` if `n` is more than the bucket ever holds.
+    pub fn try_acquire_n(&self, n: u64) -> Result<(), Duration> {
+
+        if n > self.capacity {
+            return Err(Duration::MAX);
+        }
+
+        let now = self.clock.now();
+        let mut state = self.lock();
+        let start = *state.start.get_or_insert(now);
+
+        let refills = ((now - start).as_nanos() / self.interval.as_nanos()) as u64;
+        let added = (refills - state.refills).saturating_mul(self.refill);
+        state.tokens = state.tokens.saturating_add(added).min(self.capacity);
+        state.refills = refills;
+
+        if state.tokens >= n {
+            state.tokens -= n;
+            return Ok(());
+        }
+
+        if self.refill == 0 {
+            return Err(Duration::MAX);
+        }
+
+        // refills still needed, the next one is due at the end of the current interval,
+        // in u128 nanoseconds since a refill count can be far more than a `u32` multiplier takes
+        let missing = (n - state.tokens).div_ceil(self.refill);
+        let due = (refills as u128 + missing as u128).saturating_mul(self.interval.as_nanos());
+        Err(from_nanos(due.saturating_sub((now - start).as_nanos())))
+    }
+
+    /// Tokens left right now.
+    pub fn available(&self) -> u64 {
+        let _ = self.try_acquire_n(0);
+        self.lock().tokens
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TokenState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl RateLimiter for TokenBucket {
+
+    fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_n(1)
+    }
+
+    fn clock_ref(&self) -> &dyn Clock {
+        &*self.clock
+    }
+}
+
+
+/// ## A bucket holding up to `capacity` requests which leaks one every `leak_every`.
+/// Unlike the [`TokenBucket`] it drains continuously rather than in batches, so once the burst of `capacity`
+/// is used up requests are let through evenly spaced, one per `leak_every`.
+///
+/// Kept as the time the bucket will be empty again (the "theoretical arrival time" of GCRA), no timers or queues needed.
+pub struct LeakyBucket {
+    capacity: u32,
+    leak_every: Duration,
+    clock: Arc<dyn Clock>,
+    empty_at: Mutex<Option<Instant>>,
+}
+
+impl LeakyBucket {
+
+    pub fn new(capacity: u32, leak_every: Duration) -> Self {
+        LeakyBucket { capacity: capacity.max(1), leak_every, clock: Arc::new(RealClock::default()), empty_at: Mutex::new(None) }
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl RateLimiter for LeakyBucket {
+
+    fn try_acquire(&self) -> Result<(), Duration> {
+
+        let now = self.clock.now();
+        let mut empty_at = self.empty_at.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let level = empty_at.map_or(Duration::ZERO, |empty_at| empty_at.saturating_duration_since(now));
+
+        // one more request must still fit: the level (in time) plus one drop can't exceed the capacity
+        let full = self.leak_every.checked_mul(self.capacity).unwrap_or(Duration::MAX);
+        let with_request = level.saturating_add(self.leak_every);
+        if with_request > full {
+            return Err(with_request - full);
+        }
+
+        // a bucket which would only be empty past the end of `Instant` takes nothing more
+        *empty_at = Some(now.checked_add(with_request).ok_or(Duration::MAX)?);
+        Ok(())
+    }
+
+    fn clock_ref(&self) -> &dyn Clock {
+        &*self.clock
+    }
+}
+
+
+/// `nanos` as a `Duration`, `Duration::MAX` if it's more than that.
+fn from_nanos(nanos: u128) -> Duration {
+    u64::try_from(nanos / 1_000_000_000).map_or(Duration::MAX, |secs| Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+/// ## At most `limit` permits within any `window` long stretch of time.
+/// Keeps the time of every permit handed out in the last `window` (a sliding log), so it's exact but uses
+/// memory proportional to `limit`, which is fine for the limits one sets on a ticker or a client.
+pub struct SlidingWindow {
+    limit: usize,
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    log: Mutex<VecDeque<Instant>>,
+}
+
+impl SlidingWindow {
+
+    pub fn new(limit: usize, window: Duration) -> Self {
+        SlidingWindow { limit, window, clock: Arc::new(RealClock::default()), log: Mutex::new(VecDeque::with_capacity(limit)) }
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl RateLimiter for SlidingWindow {
+
+    fn try_acquire(&self) -> Result<(), Duration> {
+
+        let now = self.clock.now();
+        let mut log = self.log.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while log.front().is_some_and(|at| now.saturating_duration_since(*at) >= self.window) {
+            log.pop_front();
+        }
+
+        if log.len() < self.limit {
+            log.push_back(now);
+            return Ok(());
+        }
+
+        // free again once the oldest permit slid out of the window
+        Err(log.front().and_then(|oldest| oldest.checked_add(self.window)).map_or(Duration::MAX, |free| free - now))
+    }
+
+    fn clock_ref(&self) -> &dyn Clock {
+        &*self.clock
+    }
+}
+
+
+/// ## Lets the first call through, then ignores everything for `interval`.
+/// Leading edge only: calls during the quiet time are dropped, not delayed (see [`Debouncer`] for that).
+pub struct Throttle {
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+    last: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+
+    pub fn new(interval: Duration) -> Self {
+        Throttle { interval, clock: Arc::new(RealClock::default()), last: Mutex::new(None) }
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Whether a call right now goes through, counting it if so.
+    pub fn ready(&self) -> bool {
+        let now = self.clock.now();
+        let mut last = self.last.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if last.is_some_and(|last| now - last < self.interval) {
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+
+    /// Runs `f` unless throttled, `None` if it was.
+    pub fn call<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+        self.ready().then(f)
+    }
+
+    /// Awaits `future` unless throttled, a throttled future is dropped without being polled.
+    pub async fn call_async<F: Future>(&self, future: F) -> Option<F::Output> {
+        match self.ready() {
+            true => Some(future.await),
+            false => None,
+        }
+    }
+
+    /// `f` turned into a closure which does nothing (returns `None`) while throttled.
+    pub fn wrap<T, R>(self, mut f: impl FnMut(T) -> R) -> impl FnMut(T) -> Option<R> {
+        move |value| self.call(|| f(value))
+    }
+}
+
+
+/// ## Runs a callback once calls have stopped coming in for `delay`, with the last value passed.
+/// Each [`Debounce::call`] pushes the deadline back, so a burst of calls ends up as a single run after the burst.
+/// ```ignore
+/// let save = Debouncer::new(Duration::from_millis(500), |text: String| write_to_disk(&text)).start();
+/// for key in keys { save.call(editor.text()); }
+/// ```
+/// The waiting is done on a thread ([`Debouncer::start`]) or a tokio task ([`Debouncer::start_async`]) sleeping
+/// on the debouncer's clock, same as a ticker. Dropping the [`Debounce`] runs a still pending call right away.
+pub struct Debouncer<T, F> {
+    delay: Duration,
+    func: F,
+    clock: Arc<dyn Clock>,
+    _value: std::marker::PhantomData<fn(T)>,
+}
+
+struct DebounceShared<T> {
+    state: Mutex<DebounceState<T>>,
+    clock: Arc<dyn Clock>,
+    notify: Notify,
+}
+
+struct DebounceState<T> {
+    pending: Option<T>,
+    deadline: Option<Instant>,
+    closed: bool,
+    // bumped on every change, so a sleeping thread knows to re-check
+    version: u64,
+}
+
+impl<T, F> Debouncer<T, F> where T: Send + 'static, F: FnMut(T) + Send + 'static, {
+
+    pub fn new(delay: Duration, func: F) -> Self {
+        Debouncer { delay, func, clock: Arc::new(RealClock::default()), _value: std::marker::PhantomData }
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn shared(&self) -> Arc<DebounceShared<T>> {
+        Arc::new(DebounceShared {
+            state: Mutex::new(DebounceState { pending: None, deadline: None, closed: false, version: 0 }),
+            clock: Arc::clone(&self.clock),
+            notify: Notify::new(),
+        })
+    }
+
+    pub fn start(mut self) -> Debounce<T> {
+        let (shared, delay) = (self.shared(), self.delay);
+        let worker = Arc::clone(&shared);
+        thread::spawn(move || {
+            loop {
+                let (value, closed) = {
+                    let mut state = worker.lock();
+                    loop {
+                        if let Some(ready) = state.take_due(worker.clock.now()) {
+                            break ready;
+                        }
+                        let (deadline, version) = (state.deadline, state.version);
+                        drop(state);
+                        worker.clock.sleep_until(deadline, &|| worker.lock().version != version);
+                        state = worker.lock();
+                    }
+                };
+                if let Some(value) = value {
+                    (self.func)(value);
+                }
+                if closed {
+                    return;
+                }
+            }
+        });
+        Debounce { shared, delay }
+    }
+
+    /// Runs the callback inside a tokio task, must be called from inside a tokio runtime.
+    pub fn start_async(mut self) -> Debounce<T> {
+        let (shared, delay) = (self.shared(), self.delay);
+        let worker = Arc::clone(&shared);
+        tokio::spawn(async move {
+            loop {
+                let (value, closed) = loop {
+                    let deadline = {
+                        let mut state = worker.lock();
+                        if let Some(ready) = state.take_due(worker.clock.now()) {
+                            break ready;
+                        }
+                        state.deadline
+                    };
+                    // `notify_one` keeps a permit, so a call between the check and here isn't lost
+                    tokio::select! {
+                        _ = worker.notify.notified() => {}
+                        _ = worker.clock.sleep_until_async(deadline) => {}
+                    }
+                };
+                if let Some(value) = value {
+                    (self.func)(value);
+                }
+                if closed {
+                    return;
+                }
+            }
+        });
+        Debounce { shared, delay }
+    }
+}
+
+impl<T> DebounceShared<T> {
+
+    fn lock(&self) -> MutexGuard<'_, DebounceState<T>> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut DebounceState<T>)) {
+        {
+            let mut state = self.lock();
+            change(&mut state);
+            state.version += 1;
+        }
+        self.clock.interrupt();
+        self.notify.notify_one();
+    }
+}
+
+impl<T> DebounceState<T> {
+    /// `(value to run with, worker should exit)` once the deadline passed or the debounce was dropped.
+    fn take_due(&mut self, now: Instant) -> Option<(Option<T>, bool)> {
+        if self.closed || self.deadline.is_some_and(|deadline| now >= deadline) {
+            self.deadline = None;
+            return Some((self.pending.take(), self.closed));
+        }
+        None
+    }
+}
+
+/// Handle to a started [`Debouncer`].
+pub struct Debounce<T> {
+    shared: Arc<DebounceShared<T>>,
+    delay: Duration,
+}
+
+impl<T> Debounce<T> {
+
+    /// Replaces the pending value and restarts the quiet time.
+    pub fn call(&self, value: T) {
+        let deadline = self.shared.clock.now() + self.delay;
+        self.shared.update(|state| {
+            state.pending = Some(value);
+            state.deadline = Some(deadline);
+        });
+    }
+
+    /// Runs a pending call now instead of after the quiet time.
+    pub fn flush(&self) {
+        let now = self.shared.clock.now();
+        self.shared.update(|state| {
+            if state.pending.is_some() {
+                state.deadline = Some(now);
+            }
+        });
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.shared.lock().pending.is_some()
+    }
+}
+
+impl<T> Drop for Debounce<T> {
+    fn drop(&mut self) {
+        self.shared.update(|state| state.closed = true);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::ManualClock;
+    use super::*;
+
+    /// Every limiter fed the same 10 requests on a manual clock, one every 100ms.
+    #[test]
+    fn limiters_on_the_same_requests() {
+        let clock = ManualClock::new();
+
+        let limiters: [Box<dyn RateLimiter>; 3] = [
+            Box::new(TokenBucket::new(3, 1, Duration::from_millis(250)).clock(clock.clone())),
+            Box::new(LeakyBucket::new(2, Duration::from_millis(200)).clock(clock.clone())),
+            Box::new(SlidingWindow::new(3, Duration::from_millis(500)).clock(clock.clone())),
+        ];
+        let throttle = Throttle::new(Duration::from_millis(250)).clock(clock.clone());
+
+        let mut passed = vec![Vec::new(); limiters.len() + 1];
+        for request in 0..10 {
+            for (passed, limiter) in passed.iter_mut().zip(&limiters) {
+                if limiter.try_acquire().is_ok() {
+                    passed.push(request);
+                }
+            }
+            if throttle.ready() {
+                passed[limiters.len()].push(request);
+            }
+            clock.advance(Duration::from_millis(100));
+        }
+
+        assert_eq!(passed[0], [0, 1, 2, 3, 5, 8], "token bucket");
+        assert_eq!(passed[1], [0, 1, 2, 4, 6, 8], "leaky bucket");
+        assert_eq!(passed[2], [0, 1, 2, 5, 6, 7], "sliding window");
+        assert_eq!(passed[3], [0, 3, 6, 9], "throttle");
+    }
+
+    #[test]
+    fn debouncer_saves_a_burst_once() {
+        let clock = ManualClock::new();
+
+        // 5 keystrokes 100ms apart, then quiet: one save, with the last text
+        let saved = Arc::new(Mutex::new(Vec::new()));
+        let saved_clone = Arc::clone(&saved);
+        let save = Debouncer::new(Duration::from_millis(300), move |text: String| saved_clone.lock().unwrap().push(text))
+            .clock(clock.clone())
+            .start();
+
+        let mut text = String::new();
+        for key in "hello".chars() {
+            text.push(key);
+            save.call(text.clone());
+            clock.wait_for_idle(1);
+            clock.advance(Duration::from_millis(100));
+        }
+        clock.wait_for_idle(1);
+        assert!(save.is_pending());
+        clock.advance(Duration::from_millis(200));
+        clock.wait_for_idle(1);
+
+        assert_eq!(*saved.lock().unwrap(), ["hello"]);
+    }
+
+    #[test]
+    fn token_bucket_waits_past_more_refills_than_a_u32_counts() {
+        let clock = ManualClock::new();
+        let bucket = TokenBucket::new(1, 1, Duration::from_nanos(1)).clock(clock.clone());
+        assert_eq!(bucket.try_acquire(), Ok(()));
+        // 5 billion refills since the bucket started, the next one is a nanosecond away
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(bucket.try_acquire(), Ok(()));
+        assert_eq!(bucket.try_acquire(), Err(Duration::from_nanos(1)));
+    }
+
+    #[test]
+    fn token_bucket_waits_for_every_refill_it_needs() {
+        let clock = ManualClock::new();
+        let bucket = TokenBucket::new(10, 2, Duration::from_millis(100)).clock(clock.clone());
+        assert_eq!(bucket.try_acquire_n(10), Ok(()));
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(bucket.try_acquire_n(5), Err(Duration::from_millis(270)));
+        assert_eq!(bucket.try_acquire_n(11), Err(Duration::MAX));
+    }
+
+    #[test]
+    fn leaky_bucket_with_a_capacity_longer_than_a_duration() {
+        let clock = ManualClock::new();
+        let bucket = LeakyBucket::new(u32::MAX, Duration::from_secs(1 << 40)).clock(clock.clone());
+        for _ in 0..3 {
+            assert_eq!(bucket.try_acquire(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn leaky_bucket_spaces_requests_out_once_full() {
+        let clock = ManualClock::new();
+        let bucket = LeakyBucket::new(2, Duration::from_millis(200)).clock(clock.clone());
+        assert_eq!(bucket.try_acquire(), Ok(()));
+        assert_eq!(bucket.try_acquire(), Ok(()));
+        assert_eq!(bucket.try_acquire(), Err(Duration::from_millis(200)));
+        clock.advance(Duration::from_millis(150));
+        assert_eq!(bucket.try_acquire(), Err(Duration::from_millis(50)));
+    }
+}
@@ -0,0 +1,58 @@
+use std::{collections::BTreeMap, sync::{Condvar, Mutex, MutexGuard}};
+
+/// ## Puts items finished in any order back into the order of their index, `0, 1, 2, ...`.
+/// [`ReorderBuffer::finish`] hands over an item and releases every item which is next in line, in order,
+/// on the thread which filled the gap. At most `window` items are waited for or held at a time:
+/// [`ReorderBuffer::wait_turn`] blocks the work on an item until it's within `window` of the next to release.
+///
+/// Whoever works on the items must start the one which is next in line without waiting for a later one
+/// (e.g. take them in order from a queue), else `wait_turn` waits forever.
+pub struct ReorderBuffer<T> {
+    state: Mutex<Pending<T>>,
+    turn: Condvar,
+    window: usize,
+}
+
+struct Pending<T> {
+    next: usize,
+    items: BTreeMap<usize, T>,
+}
+
+impl<T> ReorderBuffer<T> {
+
+    pub fn new(window: usize) -> Self {
+        ReorderBuffer { state: Mutex::new(Pending { next: 0, items: BTreeMap::new() }), turn: Condvar::new(), window: window.max(1) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pending<T>> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Blocks until `index` is less than `window` ahead of the next item to release.
+    pub fn wait_turn(&self, index: usize) {
+        let mut state = self.lock();
+        while index >= state.next.saturating_add(self.window) {
+            state = self.turn.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Calls `release` with this item and every held one after it which has no gap before it, in order.
+    /// Releases are never concurrent, `release` runs with the buffer locked.
+    pub fn finish(&self, index: usize, item: T, mut release: impl FnMut(usize, T)) {
+        let mut state = self.lock();
+        state.items.insert(index, item);
+        let mut released = false;
+        loop {
+            let next = state.next;
+            let Some(item) = state.items.remove(&next) else {
+                break;
+            };
+            release(next, item);
+            state.next += 1;
+            released = true;
+        }
+        if released {
+            self.turn.notify_all();
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::{error::Error, fmt, future::Future, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use crate::clock::{Clock, RealClock};
+
+/// ### How long to wait before retry number `n` (1 for the first retry).
+/// - `Constant`: the same delay every time.
+/// - `Linear`: `initial`, `initial + step`, `initial + 2 * step`, ...
+/// - `Exponential`: `initial`, `initial * multiplier`, `initial * multiplier^2`, ...
+/// - `DecorrelatedJitter`: a random delay between `base` and three times the previous one, spreads out clients
+///   which all failed at the same moment (see the AWS "Exponential Backoff And Jitter" article).
+///
+/// [`Retry::max_delay`] caps every one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    Constant(Duration),
+    Linear { initial: Duration, step: Duration },
+    Exponential { initial: Duration, multiplier: u32 },
+    DecorrelatedJitter { base: Duration },
+}
+
+impl Backoff {
+
+    /// Doubling from `initial`.
+    pub fn exponential(initial: Duration) -> Self {
+        Backoff::Exponential { initial, multiplier: 2 }
+    }
+
+    fn delay(&self, retry: u32, previous: Duration, rng: &mut SplitMix64) -> Duration {
+        match *self {
+            Backoff::Constant(delay) => delay,
+            Backoff::Linear { initial, step } => initial.saturating_add(step.saturating_mul(retry - 1)),
+            Backoff::Exponential { initial, multiplier } => {
+                initial.saturating_mul(multiplier.saturating_pow(retry - 1))
+            }
+            Backoff::DecorrelatedJitter { base } => {
+                let high = previous.max(base).saturating_mul(3).as_nanos().min(u64::MAX as u128) as u64;
+                let low = base.as_nanos().min(u64::MAX as u128) as u64;
+                Duration::from_nanos(low + rng.next() % (high - low).max(1))
+            }
+        }
+    }
+}
+
+/// Small and good enough for jitter, not for anything that needs real randomness.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+
+/// Why [`Retry`] stopped trying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GaveUp {
+    /// The retry predicate said the error is permanent.
+    NotRetryable,
+    MaxAttempts,
+    /// The next attempt would have started after the overall deadline.
+    Deadline,
+}
+
+/// The last error of an operation [`Retry`] gave up on.
+#[derive(Debug)]
+pub struct RetryError<E> {
+    pub error: E,
+    pub attempts: u32,
+    pub reason: GaveUp,
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            GaveUp::NotRetryable => "not retryable",
+            GaveUp::MaxAttempts => "out of attempts",
+            GaveUp::Deadline => "out of time",
+        };
+        write!(f, "gave up after {} attempt(s), {}: {}", self.attempts, reason, self.error)
+    }
+}
+
+impl<E: Error + 'static> Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+
+/// ## Runs a fallible operation again until it succeeds or it's time to give up.
+/// ```ignore
+/// let retry = Retry::new(Backoff::exponential(Duration::from_millis(100)))
+///     .max_attempts(5)
+///     .deadline(Duration::from_secs(10))
+///     .retry_if(|error: &io::Error| error.kind() != io::ErrorKind::PermissionDenied);
+/// let stream = retry.run(|| TcpStream::connect(addr))?;
+/// ```
+/// Gives up on the first error the predicate rejects, after `max_attempts` attempts, or when the next attempt
+/// would start past the `deadline` (counted from the first attempt). The delays are slept on the retry's clock,
+/// give it the [`ManualClock`](crate::clock::ManualClock) of a test and nothing really sleeps.
+pub struct Retry<E> {
+    backoff: Backoff,
+    max_attempts: Option<u32>,
+    deadline: Option<Duration>,
+    max_delay: Option<Duration>,
+    retry_if: Box<dyn Fn(&E) -> bool + Send + Sync>,
+    clock: Arc<dyn Clock>,
+    seed: Option<u64>,
+}
+
+impl<E> Retry<E> {
+
+    /// Retries every error, forever, until told otherwise.
+    pub fn new(backoff: Backoff) -> Self {
+        Retry {
+            backoff,
+            max_attempts: None,
+            deadline: None,
+            max_delay: None,
+            retry_if: Box::new(|_| true),
+            clock: Arc::new(RealClock::default()),
+            seed: None,
+        }
+    }
+
+    /// Attempts in total, including the first one.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts.max(1));
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Only errors for which `predicate` returns `true` are retried.
+    pub fn retry_if(mut self, predicate: impl Fn(&E) -> bool + Send + Sync + 'static) -> Self {
+        self.retry_if = Box::new(predicate);
+        self
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Fixes the jitter of [`Backoff::DecorrelatedJitter`], otherwise every run gets different delays.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    fn attempts(&self) -> Attempts<'_, E> {
+        static RUNS: AtomicU64 = AtomicU64::new(0);
+        let seed = self.seed.unwrap_or_else(|| {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+            nanos ^ RUNS.fetch_add(1, Ordering::Relaxed).rotate_left(32)
+        });
+        Attempts { retry: self, started: self.clock.now(), attempts: 0, delay: Duration::ZERO, rng: SplitMix64(seed) }
+    }
+
+    /// Calls `operation` until it returns `Ok` or the retry gives up, blocking the thread in between.
+    pub fn run<T>(&self, mut operation: impl FnMut() -> Result<T, E>) -> Result<T, RetryError<E>> {
+        let mut attempts = self.attempts();
+        loop {
+            let error = match operation() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            let wait = attempts.after_failure(error)?;
+            self.clock.sleep(wait);
+        }
+    }
+
+    /// Same as [`Retry::run`] for async operations, `operation` makes a new future for every attempt.
+    pub async fn run_async<T, F>(&self, mut operation: impl FnMut() -> F) -> Result<T, RetryError<E>>
+    where F: Future<Output = Result<T, E>>,
+    {
+        let mut attempts = self.attempts();
+        loop {
+            let error = match operation().await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            let wait = attempts.after_failure(error)?;
+            self.clock.sleep_async(wait).await;
+        }
+    }
+}
+
+/// The state of one `run`.
+struct Attempts<'a, E> {
+    retry: &'a Retry<E>,
+    started: Instant,
+    attempts: u32,
+    delay: Duration,
+    rng: SplitMix64,
+}
+
+impl<E> Attempts<'_, E> {
+
+    /// How long to wait before trying again, or the error to give up with.
+    fn after_failure(&mut self, error: E) -> Result<Duration, RetryError<E>> {
+
+        let retry = self.retry;
+        self.attempts += 1;
+        let attempts = self.attempts;
+        let give_up = |error, reason| RetryError { error, attempts, reason };
+
+        if !(retry.retry_if)(&error) {
+            return Err(give_up(error, GaveUp::NotRetryable));
+        }
+        if retry.max_attempts.is_some_and(|max| attempts >= max) {
+            return Err(give_up(error, GaveUp::MaxAttempts));
+        }
+
+        let delay = retry.backoff.delay(attempts, self.delay, &mut self.rng);
+        let delay = retry.max_delay.map_or(delay, |max| delay.min(max));
+        self.delay = delay;
+
+        // no point in sleeping when the attempt after it is already too late
+        let next_attempt = (retry.clock.now() - self.started).saturating_add(delay);
+        if retry.deadline.is_some_and(|deadline| next_attempt > deadline) {
+            return Err(give_up(error, GaveUp::Deadline));
+        }
+
+        Ok(delay)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{mpsc, Mutex}, thread};
+    use crate::clock::ManualClock;
+    use super::*;
+
+    /// Runs `run` on a thread, the retry sleeps on the manual clock, while this one moves the clock on by `step`
+    /// whenever it's sleeping.
+    fn on_manual_clock<R: Send + 'static>(clock: &ManualClock, step: Duration, run: impl FnOnce() -> R + Send + 'static) -> R {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(run()).unwrap());
+        loop {
+            if let Ok(result) = receiver.try_recv() {
+                return result;
+            }
+            if clock.idle_sleepers() > 0 {
+                clock.advance(step);
+            }
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn exponential_backoff_until_it_connects() {
+        let clock = ManualClock::new();
+        let started = clock.now();
+
+        // refuses 3 times, then connects
+        let connect = {
+            let clock = clock.clone();
+            let mut attempts = Vec::new();
+            move || {
+                attempts.push((clock.now() - started).as_millis());
+                match attempts.len() {
+                    1..=3 => Err(format!("connection refused (attempts at {:?}ms)", attempts)),
+                    _ => Ok(attempts.clone()),
+                }
+            }
+        };
+        let retry = Retry::new(Backoff::exponential(Duration::from_millis(100)))
+            .max_attempts(5)
+            .clock(clock.clone());
+
+        let result = on_manual_clock(&clock, Duration::from_millis(50), move || retry.run(connect));
+        assert_eq!(result.unwrap(), [0, 100, 300, 700]);
+    }
+
+    #[test]
+    fn a_permanent_error_isnt_retried() {
+        let retry = Retry::new(Backoff::Constant(Duration::from_secs(1)))
+            .deadline(Duration::from_secs(3))
+            .retry_if(|error: &String| !error.contains("denied"))
+            .clock(RealClock::default());
+        let error = retry.run(|| Err::<(), _>("permission denied".to_string())).unwrap_err();
+        assert_eq!((error.attempts, error.reason), (1, GaveUp::NotRetryable));
+    }
+
+    #[test]
+    fn decorrelated_jitter_stays_in_bounds_until_the_deadline() {
+        let clock = ManualClock::new();
+        let delays = Arc::new(Mutex::new(Vec::new()));
+        let last = Arc::new(Mutex::new(clock.now()));
+        let retry = Retry::new(Backoff::DecorrelatedJitter { base: Duration::from_millis(100) })
+            .max_delay(Duration::from_secs(2))
+            .deadline(Duration::from_secs(5))
+            .seed(7)
+            .clock(clock.clone());
+
+        let (delays_clone, last_clone, clock_clone) = (Arc::clone(&delays), Arc::clone(&last), clock.clone());
+        let error = on_manual_clock(&clock, Duration::from_millis(10), move || {
+            retry.run(|| {
+                let now = clock_clone.now();
+                let mut last = last_clone.lock().unwrap();
+                delays_clone.lock().unwrap().push((now - *last).as_millis());
+                *last = now;
+                Err::<(), _>("timeout")
+            })
+        })
+        .unwrap_err();
+        assert_eq!(error.reason, GaveUp::Deadline);
+
+        // between the base and 3x the previous delay, capped at 2s; the clock moves 10ms at a time
+        let delays = delays.lock().unwrap()[1..].to_vec();
+        assert!(delays.len() >= 3, "{:?}", delays);
+        for (previous, delay) in [100].iter().chain(&delays).zip(&delays) {
+            assert!((100..=2_010).contains(delay) && *delay <= previous * 3 + 10, "{:?}", delays);
+        }
+    }
+}
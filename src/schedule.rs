@@ -0,0 +1,405 @@
+use std::{fmt, str::FromStr, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use crate::clock::Clock;
+
+/// ## When a [`Ticker`](crate::ticker::Ticker) fires.
+/// Either a fixed interval or a cron expression, parsed with `"...".parse::<Schedule>()`:
+/// - the classic 5 fields `minute hour day-of-month month day-of-week`, each field takes `*`, numbers, lists `1,15`,
+///   ranges `1-5`, steps `*/5` or `10-40/10`, and names for months (`JAN`-`DEC`) and week days (`SUN`-`SAT`, `0` and `7` both being sunday),
+/// - `@every <duration>` like `@every 5s`, `@every 1h30m` or `@every 250ms`,
+/// - `@yearly` (`@annually`), `@monthly`, `@weekly`, `@daily` (`@midnight`) and `@hourly`.
+///
+/// Like the original cron, when both day-of-month and day-of-week are restricted a day matching *either* of them fires.
+/// ### Note: cron expressions are evaluated in UTC, there is no time zone support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    kind: ScheduleKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ScheduleKind {
+    Every(Duration),
+    Cron(Cron),
+}
+
+/// One bit per allowed value of each field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // `*` in the day fields, which decides how they are combined
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseScheduleError {
+    /// A cron expression needs exactly 5 fields, this is how many were found.
+    FieldCount(usize),
+    InvalidField { field: &'static str, value: String },
+    InvalidDuration(String),
+    UnknownShorthand(String),
+}
+
+impl fmt::Display for ParseScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseScheduleError::FieldCount(count) => write!(f, "expected 5 cron fields, found {}", count),
+            ParseScheduleError::InvalidField { field, value } => write!(f, "invalid {} field: {:?}", field, value),
+            ParseScheduleError::InvalidDuration(value) => write!(f, "invalid duration: {:?}", value),
+            ParseScheduleError::UnknownShorthand(value) => write!(f, "unknown schedule shorthand: {:?}", value),
+        }
+    }
+}
+
+impl std::error::Error for ParseScheduleError {}
+
+
+const MONTH_NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEK_DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl FromStr for Schedule {
+
+    type Err = ParseScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        let s = s.trim();
+
+        if let Some(duration) = s.strip_prefix("@every") {
+            return Ok(Schedule::every(parse_duration(duration.trim())?));
+        }
+
+        let expression = match s {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            _ if s.starts_with('@') => return Err(ParseScheduleError::UnknownShorthand(s.to_string())),
+            _ => s,
+        };
+
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(ParseScheduleError::FieldCount(fields.len()));
+        };
+
+        // 7 is sunday too, fold it onto 0
+        let mut week_days = parse_field("day-of-week", days_of_week, 0, 7, &WEEK_DAY_NAMES)?;
+        if week_days & (1 << 7) != 0 {
+            week_days = (week_days & !(1 << 7)) | 1;
+        }
+
+        Ok(Schedule {
+            kind: ScheduleKind::Cron(Cron {
+                minutes: parse_field("minute", minutes, 0, 59, &[])?,
+                hours: parse_field("hour", hours, 0, 23, &[])?,
+                days_of_month: parse_field("day-of-month", days_of_month, 1, 31, &[])?,
+                months: parse_field("month", months, 1, 12, &MONTH_NAMES)?,
+                days_of_week: week_days,
+                any_day_of_month: days_of_month.starts_with('*'),
+                any_day_of_week: days_of_week.starts_with('*'),
+            }),
+        })
+    }
+}
+
+/// `names[0]` stands for `min`, `names[1]` for `min + 1` and so on.
+fn parse_field(field: &'static str, value: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, ParseScheduleError> {
+
+    let invalid = || ParseScheduleError::InvalidField { field, value: value.to_string() };
+
+    let parse_value = |part: &str| -> Result<u32, ParseScheduleError> {
+        let number = match names.iter().position(|name| name.eq_ignore_ascii_case(part)) {
+            Some(index) => index as u32 + min,
+            None => part.parse::<u32>().map_err(|_| invalid())?,
+        };
+        if (min..=max).contains(&number) { Ok(number) } else { Err(invalid()) }
+    };
+
+    let mut bits = 0u64;
+
+    for part in value.split(',') {
+
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?)),
+            None => (part, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+            // `5/15` means from 5 to the end in steps of 15
+            None if step.is_some() => (parse_value(range)?, max),
+            None => (parse_value(range)?, parse_value(range)?),
+        };
+
+        if start > end {
+            return Err(invalid());
+        }
+
+        for number in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << number;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// `5s`, `1h30m`, `250ms`, units are `ms`, `s`, `m`, `h` and `d`.
+fn parse_duration(value: &str) -> Result<Duration, ParseScheduleError> {
+
+    let invalid = || ParseScheduleError::InvalidDuration(value.to_string());
+
+    let mut total = Duration::ZERO;
+    let mut rest = value;
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let amount = rest[..digits].parse::<u64>().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(1),
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            "d" => Duration::from_secs(24 * 60 * 60),
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+
+        let amount = u32::try_from(amount).map_err(|_| invalid())?;
+        total = unit.checked_mul(amount).and_then(|part| total.checked_add(part)).ok_or_else(invalid)?;
+    }
+
+    if total.is_zero() { Err(invalid()) } else { Ok(total) }
+}
+
+
+impl Schedule {
+
+    /// Fires every `interval`, the first time right away.
+    pub fn every(interval: Duration) -> Self {
+        Schedule { kind: ScheduleKind::Every(interval) }
+    }
+
+    /// `Some` for a fixed interval schedule, `None` for a cron expression.
+    pub fn interval(&self) -> Option<Duration> {
+        match self.kind {
+            ScheduleKind::Every(interval) => Some(interval),
+            ScheduleKind::Cron(_) => None,
+        }
+    }
+
+    /// The first time strictly after `t` this schedule fires.
+    /// - for `@every` that's simply `t + interval`,
+    /// - for a cron expression it's the next whole minute matching all fields, `None` if nothing matches
+    ///   within the next 10 years (e.g. `0 0 30 2 *`).
+    pub fn next_fire_after(&self, t: SystemTime) -> Option<SystemTime> {
+        match &self.kind {
+            ScheduleKind::Every(interval) => Some(t + *interval),
+            ScheduleKind::Cron(cron) => cron.next_after(unix_seconds(t)).map(from_unix_seconds),
+        }
+    }
+
+    /// When a ticker starting at `start` fires first: right away for an interval, the next match for cron.
+    pub(crate) fn first_instant(&self, start: Instant, clock: &dyn Clock) -> Option<Instant> {
+        match self.kind {
+            ScheduleKind::Every(_) => Some(start),
+            ScheduleKind::Cron(_) => self.next_instant_after(start, clock),
+        }
+    }
+
+    /// [`Schedule::next_fire_after`] on the `Instant` timeline of `clock`.
+    pub(crate) fn next_instant_after(&self, after: Instant, clock: &dyn Clock) -> Option<Instant> {
+
+        if let ScheduleKind::Every(interval) = self.kind {
+            return Some(after + interval);
+        }
+
+        let (now, system_now) = (clock.now(), clock.system_now());
+        let after = if after >= now { system_now + (after - now) } else { system_now - (now - after) };
+        let fire = self.next_fire_after(after)?;
+
+        Some(match fire.duration_since(system_now) {
+            Ok(ahead) => now + ahead,
+            Err(behind) => now - behind.duration(),
+        })
+    }
+}
+
+impl Cron {
+
+    fn next_after(&self, t: i64) -> Option<i64> {
+
+        const MINUTE: i64 = 60;
+        const HOUR: i64 = 60 * MINUTE;
+        const DAY: i64 = 24 * HOUR;
+
+        let give_up = t + 10 * 366 * DAY;
+        let mut t = (t.div_euclid(MINUTE) + 1) * MINUTE;
+
+        while t < give_up {
+
+            let days = t.div_euclid(DAY);
+            let (year, month, day) = civil_from_days(days);
+
+            if self.months & (1 << month) == 0 {
+                // first day of the next month
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                t = days_from_civil(year, month, 1) * DAY;
+                continue;
+            }
+
+            if !self.day_matches(day, days) {
+                t = (days + 1) * DAY;
+                continue;
+            }
+
+            if self.hours & (1 << (t.rem_euclid(DAY) / HOUR)) == 0 {
+                t = (t.div_euclid(HOUR) + 1) * HOUR;
+                continue;
+            }
+
+            if self.minutes & (1 << (t.rem_euclid(HOUR) / MINUTE)) == 0 {
+                t += MINUTE;
+                continue;
+            }
+
+            return Some(t);
+        }
+
+        None
+    }
+
+    fn day_matches(&self, day_of_month: u32, days_since_epoch: i64) -> bool {
+        // 1970-01-01 was a thursday
+        let week_day = (days_since_epoch + 4).rem_euclid(7);
+        let month_day_ok = self.days_of_month & (1 << day_of_month) != 0;
+        let week_day_ok = self.days_of_week & (1 << week_day) != 0;
+
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => month_day_ok || week_day_ok,
+            _ => month_day_ok && week_day_ok,
+        }
+    }
+}
+
+
+fn unix_seconds(t: SystemTime) -> i64 {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs_f64().ceil() as i64),
+    }
+}
+
+fn from_unix_seconds(seconds: i64) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    }
+}
+
+/// Howard Hinnant's `days_from_civil`: days since 1970-01-01 of a proleptic gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of [`days_from_civil`], gives `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC, handy for printing fire times.
+pub fn format_utc(t: SystemTime) -> String {
+    let seconds = unix_seconds(t);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// The UTC `SystemTime` of a calendar date and time, handy to start a [`ManualClock`](crate::clock::ManualClock) at.
+pub fn utc(year: i64, month: u32, day: u32, hour: u32, minute: u32) -> SystemTime {
+    from_unix_seconds(days_from_civil(year, month, day) * 86400 + hour as i64 * 3600 + minute as i64 * 60)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_fire_times() {
+        // a thursday
+        let t = utc(2026, 1, 1, 12, 34);
+
+        let cases = [
+            ("*/5 * * * *", "2026-01-01 12:35:00"),
+            ("0 3 * * *", "2026-01-02 03:00:00"),
+            ("30 9 * * MON-FRI", "2026-01-02 09:30:00"),
+            ("0 0 1 */3 *", "2026-04-01 00:00:00"),
+            ("0 12 13 * 5", "2026-01-02 12:00:00"),
+            ("0 0 29 2 *", "2028-02-29 00:00:00"),
+            ("@daily", "2026-01-02 00:00:00"),
+            ("@every 1h30m", "2026-01-01 14:04:00"),
+        ];
+        for (expression, expected) in cases {
+            let schedule = expression.parse::<Schedule>().unwrap();
+            assert_eq!(format_utc(schedule.next_fire_after(t).unwrap()), expected, "{}", expression);
+        }
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        let minute = |value: &str| ParseScheduleError::InvalidField { field: "minute", value: value.to_string() };
+        let cases = [
+            ("* * * *", ParseScheduleError::FieldCount(4)),
+            ("60 * * * *", minute("60")),
+            ("*/0 * * * *", minute("*/0")),
+            ("5-1 * * * *", minute("5-1")),
+            ("@every", ParseScheduleError::InvalidDuration(String::new())),
+            ("@every 5x", ParseScheduleError::InvalidDuration("5x".to_string())),
+            ("@fortnightly", ParseScheduleError::UnknownShorthand("@fortnightly".to_string())),
+        ];
+        for (expression, error) in cases {
+            assert_eq!(expression.parse::<Schedule>(), Err(error), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn every_parses_its_duration() {
+        assert_eq!("@every 1h30m".parse::<Schedule>().unwrap().interval(), Some(Duration::from_secs(90 * 60)));
+        assert_eq!("@every 250ms".parse::<Schedule>().unwrap().interval(), Some(Duration::from_millis(250)));
+        assert_eq!("@every 4294967295s".parse::<Schedule>().unwrap().interval(), Some(Duration::from_secs(u32::MAX as u64)));
+    }
+
+    #[test]
+    fn every_rejects_amounts_which_dont_fit() {
+        // 5000000000 as u32 would have been 705032704s
+        for bad in ["@every 5000000000s", "@every 4294967296ms", "@every 18446744073709551616s"] {
+            let duration = bad.trim_start_matches("@every ").to_string();
+            assert_eq!(bad.parse::<Schedule>(), Err(ParseScheduleError::InvalidDuration(duration)));
+        }
+    }
+}
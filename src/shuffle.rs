@@ -0,0 +1,56 @@
+use std::{collections::BTreeMap, hash::{DefaultHasher, Hash, Hasher}};
+
+/// ## Decides which reducer gets a key.
+/// Every value of a key ends up at the same reducer, which is all the reduce phase needs,
+/// how the keys are spread over the reducers is up to the partitioner:
+/// - [`HashPartitioner`]: evenly, in no particular order,
+/// - [`RangePartitioner`]: by ranges of keys, reducer `i` gets smaller keys than reducer `i + 1`.
+pub trait Partitioner<K>: Send + Sync {
+    /// A reducer below `reducers`, the same one every time for the same key.
+    fn partition(&self, key: &K, reducers: usize) -> usize;
+}
+
+/// The hash of the key modulo the number of reducers.
+/// The hasher has fixed keys, so a key goes to the same reducer in every run (of the same build).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HashPartitioner;
+
+impl<K: Hash> Partitioner<K> for HashPartitioner {
+    fn partition(&self, key: &K, reducers: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % reducers.max(1) as u64) as usize
+    }
+}
+
+/// ## Splits the keys at fixed bounds.
+/// Reducer 0 gets everything below the first bound, reducer `i` everything from bound `i - 1` up to bound `i`,
+/// so the reducers' outputs one after the other are sorted, like the part files of a Hadoop TeraSort.
+/// `n` bounds are meant for `n + 1` reducers, with fewer the last reducer takes the rest.
+#[derive(Debug, Clone)]
+pub struct RangePartitioner<K> {
+    bounds: Vec<K>,
+}
+
+impl<K: Ord> RangePartitioner<K> {
+    pub fn new(mut bounds: Vec<K>) -> Self {
+        bounds.sort();
+        RangePartitioner { bounds }
+    }
+}
+
+impl<K: Ord + Send + Sync> Partitioner<K> for RangePartitioner<K> {
+    fn partition(&self, key: &K, reducers: usize) -> usize {
+        self.bounds.partition_point(|bound| bound <= key).min(reducers.max(1) - 1)
+    }
+}
+
+/// Routes the map output of every segment to the reducers' partitions, the values of a key stay in segment order.
+pub(crate) fn shuffle_into<K: Ord, V>(partitions: &mut [BTreeMap<K, Vec<V>>], mapped: Vec<BTreeMap<K, Vec<V>>>, partitioner: &dyn Partitioner<K>) {
+    for segment in mapped {
+        for (key, values) in segment {
+            let reducer = partitioner.partition(&key, partitions.len()).min(partitions.len() - 1);
+            partitions[reducer].entry(key).or_default().extend(values);
+        }
+    }
+}
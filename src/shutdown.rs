@@ -0,0 +1,387 @@
+use std::{future::Future, pin::pin, sync::{Arc, Condvar, Mutex, MutexGuard}, task::{Context, Poll, Waker}, thread, time::{Duration, Instant}};
+use tokio::sync::Notify;
+use crate::ticker::TickerHandle;
+
+/// ## A stop signal which can be cloned into any ticker, thread or task.
+/// - threads check [`CancellationToken::is_cancelled`] or sleep with [`CancellationToken::wait_timeout`], which returns early on cancel,
+/// - tasks put [`CancellationToken::cancelled`] into a `tokio::select!` (same idea as the `Notify` in `async_ticker_with_notification_mechanism`),
+/// - a [`Ticker`](crate::ticker::Ticker) takes it through `.cancel_token(token)` and stops by itself.
+///
+/// Cancelling is one way, a cancelled token stays cancelled.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+#[derive(Default)]
+struct TokenInner {
+    cancelled: Mutex<bool>,
+    condvar: Condvar,
+    notify: Notify,
+    on_cancel: Mutex<Callbacks>,
+}
+
+/// The [`CancellationToken::on_cancel`] callbacks still waiting for a cancel, by registration id.
+#[derive(Default)]
+struct Callbacks {
+    next_id: u64,
+    waiting: Vec<(u64, Box<dyn FnOnce() + Send>)>,
+}
+
+impl CancellationToken {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, bool> {
+        self.inner.cancelled.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn callbacks(&self) -> MutexGuard<'_, Callbacks> {
+        self.inner.on_cancel.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn cancel(&self) {
+        {
+            let mut cancelled = self.lock();
+            if *cancelled {
+                return;
+            }
+            *cancelled = true;
+        }
+        self.inner.condvar.notify_all();
+        self.inner.notify.notify_waiters();
+
+        let callbacks = std::mem::take(&mut self.callbacks().waiting);
+        for (_, callback) in callbacks {
+            callback();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.lock()
+    }
+
+    /// Blocks until cancelled.
+    pub fn wait(&self) {
+        let mut cancelled = self.lock();
+        while !*cancelled {
+            cancelled = self.inner.condvar.wait(cancelled).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Sleeps for `timeout` unless cancelled earlier, returns whether the token is cancelled.
+    /// Handy as the sleep of a thread loop: `while !token.wait_timeout(interval) { ... }`.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut cancelled = self.lock();
+        while !*cancelled {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            cancelled = self.inner.condvar
+                .wait_timeout(cancelled, deadline - now)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+        *cancelled
+    }
+
+    /// Resolves once cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Runs `callback` on cancel, right away if the token is already cancelled.
+    /// The callback stays registered for as long as the returned [`OnCancel`] is kept, a long-lived token
+    /// doesn't pile up the callbacks of everybody who has long stopped listening.
+    pub fn on_cancel(&self, callback: impl FnOnce() + Send + 'static) -> OnCancel {
+        {
+            let cancelled = self.lock();
+            if !*cancelled {
+                // still holding the flag lock, so a concurrent `cancel()` will find the callback
+                let mut callbacks = self.callbacks();
+                let id = callbacks.next_id;
+                callbacks.next_id += 1;
+                callbacks.waiting.push((id, Box::new(callback)));
+                return OnCancel { token: self.clone(), id: Some(id) };
+            }
+        }
+        callback();
+        OnCancel { token: self.clone(), id: None }
+    }
+}
+
+/// A callback registered with [`CancellationToken::on_cancel`], dropping it unregisters the callback unless it ran already.
+#[must_use = "dropping it unregisters the callback right away"]
+pub struct OnCancel {
+    token: CancellationToken,
+    id: Option<u64>,
+}
+
+impl Drop for OnCancel {
+    fn drop(&mut self) {
+        let Some(id) = self.id else { return };
+        let removed = {
+            let mut callbacks = self.token.callbacks();
+            let position = callbacks.waiting.iter().position(|(waiting, _)| *waiting == id);
+            position.map(|position| callbacks.waiting.swap_remove(position))
+        };
+        // dropped outside of the lock, whatever the callback owns may have a drop of its own
+        drop(removed);
+    }
+}
+
+
+/// How a registered worker ended up after [`Shutdown::shutdown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskOutcome {
+    /// Noticed the token and finished within the drain deadline.
+    Stopped,
+    /// Finished within the deadline, but by panicking.
+    Panicked,
+    /// Still running at the deadline: tasks get aborted, threads can't be killed in Rust so they are left running detached.
+    Aborted,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownReport {
+    pub outcomes: Vec<(String, TaskOutcome)>,
+}
+
+impl ShutdownReport {
+
+    pub fn with_outcome(&self, outcome: TaskOutcome) -> Vec<&str> {
+        self.outcomes.iter().filter(|(_, o)| *o == outcome).map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn stopped(&self) -> Vec<&str> {
+        self.with_outcome(TaskOutcome::Stopped)
+    }
+
+    pub fn aborted(&self) -> Vec<&str> {
+        self.with_outcome(TaskOutcome::Aborted)
+    }
+
+    /// Everybody stopped by themselves in time.
+    pub fn is_clean(&self) -> bool {
+        self.outcomes.iter().all(|(_, outcome)| *outcome == TaskOutcome::Stopped)
+    }
+}
+
+
+enum Worker {
+    Thread(thread::JoinHandle<()>),
+    Task(tokio::task::JoinHandle<()>),
+    Ticker(TickerHandle),
+}
+
+impl Worker {
+    fn is_finished(&self) -> bool {
+        match self {
+            Worker::Thread(handle) => handle.is_finished(),
+            Worker::Task(handle) => handle.is_finished(),
+            Worker::Ticker(handle) => handle.is_finished(),
+        }
+    }
+}
+
+/// ## Stops everything registered with it, waits a bounded time and says how it went.
+/// ```ignore
+/// let mut shutdown = Shutdown::new();
+/// let token = shutdown.token();
+/// shutdown.register_task("poller", tokio::spawn(async move { token.cancelled().await }));
+/// shutdown.register_ticker("metrics", Ticker::new(interval, flush).cancel_token(shutdown.token()).start());
+/// let report = shutdown.shutdown(Duration::from_secs(5)).await;
+/// ```
+/// [`Shutdown::shutdown`] cancels the token (and stops registered tickers), then gives all workers
+/// until the drain deadline to finish what they are doing. Whatever is still running after that gets aborted.
+#[derive(Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    workers: Vec<(String, Worker)>,
+}
+
+impl Shutdown {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A clone of the token every registered worker should be watching.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn register_thread(&mut self, name: impl Into<String>, handle: thread::JoinHandle<()>) {
+        self.workers.push((name.into(), Worker::Thread(handle)));
+    }
+
+    pub fn register_task(&mut self, name: impl Into<String>, handle: tokio::task::JoinHandle<()>) {
+        self.workers.push((name.into(), Worker::Task(handle)));
+    }
+
+    /// Tickers get `stop()`ped on shutdown even if they weren't given the token.
+    pub fn register_ticker(&mut self, name: impl Into<String>, handle: TickerHandle) {
+        self.workers.push((name.into(), Worker::Ticker(handle)));
+    }
+
+    fn begin(&self) {
+        self.token.cancel();
+        for (_, worker) in &self.workers {
+            if let Worker::Ticker(handle) = worker {
+                handle.stop();
+            }
+        }
+    }
+
+    /// Cancels, waits at most `drain` for every worker to exit and aborts the rest.
+    pub async fn shutdown(self, drain: Duration) -> ShutdownReport {
+        self.begin();
+        let deadline = Instant::now() + drain;
+        while Instant::now() < deadline && !self.workers.iter().all(|(_, worker)| worker.is_finished()) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        self.report()
+    }
+
+    /// Same as [`Shutdown::shutdown`] for code outside of a tokio runtime.
+    pub fn shutdown_blocking(self, drain: Duration) -> ShutdownReport {
+        self.begin();
+        let deadline = Instant::now() + drain;
+        while Instant::now() < deadline && !self.workers.iter().all(|(_, worker)| worker.is_finished()) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.report()
+    }
+
+    fn report(self) -> ShutdownReport {
+
+        let outcomes = self.workers.into_iter().map(|(name, worker)| {
+
+            if !worker.is_finished() {
+                match &worker {
+                    Worker::Task(handle) => handle.abort(),
+                    Worker::Ticker(handle) => handle.abort(),
+                    Worker::Thread(_) => {}
+                }
+                return (name, TaskOutcome::Aborted);
+            }
+
+            // everything is finished here, so none of these joins block
+            let result = match worker {
+                Worker::Thread(handle) => handle.join().is_ok(),
+                Worker::Task(handle) => matches!(poll_finished(handle), Ok(())),
+                Worker::Ticker(handle) => handle.join_finished().is_ok(),
+            };
+
+            (name, if result { TaskOutcome::Stopped } else { TaskOutcome::Panicked })
+        });
+
+        ShutdownReport { outcomes: outcomes.collect() }
+    }
+}
+
+/// Takes the result out of a task which `is_finished()`, without needing a runtime to `.await` it.
+pub(crate) fn poll_finished<T>(handle: tokio::task::JoinHandle<T>) -> Result<T, tokio::task::JoinError> {
+    // `unconstrained` so tokio's coop budget can't make a finished task look pending
+    let mut handle = pin!(tokio::task::unconstrained(handle));
+    loop {
+        if let Poll::Ready(result) = handle.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+            return result;
+        }
+        thread::yield_now();
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use crate::clock::{Clock, ManualClock};
+    use super::*;
+
+    /// Three kinds of workers sharing one token: a ticker, a polite task and a stubborn thread which ignores it.
+    #[tokio::test]
+    async fn shutdown_stops_the_polite_and_aborts_the_stubborn() {
+        let clock = ManualClock::new();
+        let mut shutdown = Shutdown::new();
+
+        let ticker = crate::ticker::Ticker::new(Duration::from_millis(200), || {})
+            .clock(clock.clone())
+            .cancel_token(shutdown.token())
+            .start_async();
+        shutdown.register_ticker("ticker", ticker);
+
+        let (token, poller_clock) = (shutdown.token(), clock.clone());
+        shutdown.register_task("poller", tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = poller_clock.sleep_async(Duration::from_millis(300)) => {}
+                }
+            }
+        }));
+
+        // blocks until the test lets it go at the end
+        let (release, released) = mpsc::channel::<()>();
+        shutdown.register_thread("stubborn", thread::spawn(move || {
+            let _ = released.recv();
+        }));
+
+        // a few ticks and polls go by
+        clock.idle(2).await;
+        for _ in 0..5 {
+            clock.advance(Duration::from_millis(100));
+            clock.idle(2).await;
+        }
+
+        let report = shutdown.shutdown(Duration::from_millis(100)).await;
+        drop(release);
+        assert_eq!(report.stopped(), ["ticker", "poller"]);
+        assert_eq!(report.aborted(), ["stubborn"]);
+    }
+
+    #[test]
+    fn a_dropped_callback_never_runs() {
+        let token = CancellationToken::new();
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let register = |name| {
+            let ran = Arc::clone(&ran);
+            token.on_cancel(move || ran.lock().unwrap().push(name))
+        };
+        let kept = register("kept");
+        drop(register("dropped"));
+        assert_eq!(token.callbacks().waiting.len(), 1);
+
+        token.cancel();
+        assert_eq!(*ran.lock().unwrap(), ["kept"]);
+        drop(kept);
+        // already cancelled, it runs right away
+        drop(register("late"));
+        assert_eq!(*ran.lock().unwrap(), ["kept", "late"]);
+    }
+
+    /// Tickers restarted again and again under one token leave none of their callbacks behind.
+    #[test]
+    fn stopped_tickers_unregister_from_the_token() {
+        let token = CancellationToken::new();
+        for _ in 0..10 {
+            let ticker = crate::ticker::Ticker::new(Duration::from_secs(60), || {}).cancel_token(token.clone()).start();
+            ticker.stop();
+            ticker.join().unwrap();
+        }
+        assert_eq!(token.callbacks().waiting.len(), 0);
+    }
+}
@@ -0,0 +1,337 @@
+use std::{cmp::{Ordering, Reverse}, collections::{btree_map, BTreeMap, BTreeSet, BinaryHeap}, fs, io::{self, BufReader, BufWriter, Read, Write}, path::{Path, PathBuf}, sync::atomic::{AtomicU64, Ordering as AtomicOrdering}};
+
+/// ## Keys and values which can be written to a spill file and read back.
+/// A plain binary encoding for the same build on the same machine, integers in little endian (`usize` as is),
+/// strings and collections with their length up front. Implemented for the integers, `bool`, `()`, `String`,
+/// `Vec`, `BTreeSet`, `BTreeMap`, `Option`, `Result` and tuples up to three, which covers the example jobs.
+/// The map-reduce workers (see `cluster.rs`) send their tasks and results in it as well.
+pub trait Spill: Sized {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()>;
+    fn read_from(input: &mut dyn Read) -> io::Result<Self>;
+}
+
+/// Collections grow as their items arrive, a corrupt or hostile length doesn't allocate more than this up front.
+const PREALLOCATE: usize = 1024;
+
+macro_rules! spill_integers {
+    ($($integer:ty),*) => {$(
+        impl Spill for $integer {
+            fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+                out.write_all(&self.to_le_bytes())
+            }
+            fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+                let mut bytes = [0; size_of::<$integer>()];
+                input.read_exact(&mut bytes)?;
+                Ok(<$integer>::from_le_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+spill_integers!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl Spill for () {
+    fn write_to(&self, _: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+    fn read_from(_: &mut dyn Read) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl Spill for bool {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        (*self as u8).write_to(out)
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        Ok(u8::read_from(input)? != 0)
+    }
+}
+
+impl Spill for String {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.len().write_to(out)?;
+        out.write_all(self.as_bytes())
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        let length = usize::read_from(input)?;
+        let mut bytes = Vec::with_capacity(length.min(PREALLOCATE));
+        Read::take(&mut *input, length as u64).read_to_end(&mut bytes)?;
+        if bytes.len() < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+impl<T: Spill> Spill for Vec<T> {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.len().write_to(out)?;
+        self.iter().try_for_each(|item| item.write_to(out))
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        let length = usize::read_from(input)?;
+        let mut items = Vec::with_capacity(length.min(PREALLOCATE));
+        for _ in 0..length {
+            items.push(T::read_from(input)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Spill + Ord> Spill for BTreeSet<T> {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.len().write_to(out)?;
+        self.iter().try_for_each(|item| item.write_to(out))
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        (0..usize::read_from(input)?).map(|_| T::read_from(input)).collect()
+    }
+}
+
+impl<T: Spill> Spill for Option<T> {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.is_some().write_to(out)?;
+        self.as_ref().map_or(Ok(()), |value| value.write_to(out))
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        bool::read_from(input)?.then(|| T::read_from(input)).transpose()
+    }
+}
+
+impl<K: Spill + Ord, V: Spill> Spill for BTreeMap<K, V> {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.len().write_to(out)?;
+        self.iter().try_for_each(|(key, value)| {
+            key.write_to(out)?;
+            value.write_to(out)
+        })
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        (0..usize::read_from(input)?).map(|_| Ok((K::read_from(input)?, V::read_from(input)?))).collect()
+    }
+}
+
+impl<T: Spill, E: Spill> Spill for Result<T, E> {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.is_ok().write_to(out)?;
+        match self {
+            Ok(value) => value.write_to(out),
+            Err(error) => error.write_to(out),
+        }
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        Ok(match bool::read_from(input)? {
+            true => Ok(T::read_from(input)?),
+            false => Err(E::read_from(input)?),
+        })
+    }
+}
+
+impl<A: Spill, B: Spill> Spill for (A, B) {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.0.write_to(out)?;
+        self.1.write_to(out)
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        Ok((A::read_from(input)?, B::read_from(input)?))
+    }
+}
+
+impl<A: Spill, B: Spill, C: Spill> Spill for (A, B, C) {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.0.write_to(out)?;
+        self.1.write_to(out)?;
+        self.2.write_to(out)
+    }
+    fn read_from(input: &mut dyn Read) -> io::Result<Self> {
+        Ok((A::read_from(input)?, B::read_from(input)?, C::read_from(input)?))
+    }
+}
+
+
+/// A sorted run on disk, deleted when dropped (after it was merged, or when the job failed).
+pub(crate) struct RunFile {
+    path: PathBuf,
+}
+
+impl Drop for RunFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The keys of a run come in ascending order, each as `1`, the key and its values, a `0` ends the run.
+pub(crate) fn write_run<K: Spill, V: Spill>(directory: &Path, run: &BTreeMap<K, Vec<V>>) -> io::Result<RunFile> {
+    static RUNS: AtomicU64 = AtomicU64::new(0);
+    let name = format!("lrn-rs-spill-{}-{}.run", std::process::id(), RUNS.fetch_add(1, AtomicOrdering::Relaxed));
+    let file = RunFile { path: directory.join(name) };
+
+    let mut out = BufWriter::new(fs::File::create(&file.path)?);
+    for (key, values) in run {
+        true.write_to(&mut out)?;
+        key.write_to(&mut out)?;
+        values.write_to(&mut out)?;
+    }
+    false.write_to(&mut out)?;
+    out.flush()?;
+    Ok(file)
+}
+
+/// Reads a run back, one key at a time.
+pub(crate) fn read_run<K: Spill + 'static, V: Spill + 'static>(file: RunFile) -> io::Result<Run<K, V>> {
+    let mut input = BufReader::new(fs::File::open(&file.path)?);
+    let mut done = false;
+    Ok(Run::Disk(Box::new(std::iter::from_fn(move || {
+        // keeps the file around while it's read
+        let _file = &file;
+        if done {
+            return None;
+        }
+        let entry = bool::read_from(&mut input).and_then(|more| match more {
+            true => Ok(Some((K::read_from(&mut input)?, Vec::<V>::read_from(&mut input)?))),
+            false => Ok(None),
+        });
+        done = !matches!(entry, Ok(Some(_)));
+        entry.transpose()
+    }))))
+}
+
+/// Keys in ascending order with their values, what's still in memory or a run read back from disk.
+pub(crate) enum Run<K, V> {
+    Memory(btree_map::IntoIter<K, Vec<V>>),
+    Disk(Box<dyn Iterator<Item = io::Result<(K, Vec<V>)>> + Send>),
+}
+
+impl<K, V> Iterator for Run<K, V> {
+    type Item = io::Result<(K, Vec<V>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Run::Memory(entries) => entries.next().map(Ok),
+            Run::Disk(entries) => entries.next(),
+        }
+    }
+}
+
+/// The next key of one run, the heap orders by key and then by run, so equal keys come out in run order.
+struct Head<K, V> {
+    key: K,
+    values: Vec<V>,
+    run: usize,
+}
+
+impl<K: Ord, V> PartialEq for Head<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K: Ord, V> Eq for Head<K, V> {}
+
+impl<K: Ord, V> PartialOrd for Head<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for Head<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.key, self.run).cmp(&(&other.key, other.run))
+    }
+}
+
+/// ## k-way merge of sorted runs.
+/// Yields every key once, in ascending order, with the values of all runs which had it, in run order.
+/// Only the current key of every run is in memory.
+pub(crate) struct Merge<K, V> {
+    runs: Vec<Run<K, V>>,
+    heap: BinaryHeap<Reverse<Head<K, V>>>,
+    failed: bool,
+}
+
+impl<K: Ord, V> Merge<K, V> {
+
+    pub(crate) fn new(runs: Vec<Run<K, V>>) -> io::Result<Self> {
+        let mut merge = Merge { runs, heap: BinaryHeap::new(), failed: false };
+        for run in 0..merge.runs.len() {
+            merge.advance(run)?;
+        }
+        Ok(merge)
+    }
+
+    fn advance(&mut self, run: usize) -> io::Result<()> {
+        if let Some((key, values)) = self.runs[run].next().transpose()? {
+            self.heap.push(Reverse(Head { key, values, run }));
+        }
+        Ok(())
+    }
+
+    fn next_key(&mut self) -> io::Result<Option<(K, Vec<V>)>> {
+        let Some(Reverse(Head { key, mut values, run })) = self.heap.pop() else {
+            return Ok(None);
+        };
+        self.advance(run)?;
+        while self.heap.peek().is_some_and(|Reverse(head)| head.key == key) {
+            let Reverse(head) = self.heap.pop().expect("just peeked");
+            values.extend(head.values);
+            self.advance(head.run)?;
+        }
+        Ok(Some((key, values)))
+    }
+}
+
+impl<K: Ord, V> Iterator for Merge<K, V> {
+
+    /// An error ends the merge.
+    type Item = io::Result<(K, Vec<V>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.next_key();
+        self.failed = next.is_err();
+        next.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Spill>(value: &T) -> io::Result<T> {
+        let mut bytes = Vec::new();
+        value.write_to(&mut bytes)?;
+        T::read_from(&mut bytes.as_slice())
+    }
+
+    /// A length with nothing (or not enough) after it, as a truncated file or a hostile peer sends.
+    fn claiming(length: usize, rest: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        length.write_to(&mut bytes).unwrap();
+        bytes.extend_from_slice(rest);
+        bytes
+    }
+
+    #[test]
+    fn values_come_back_as_they_went() {
+        let value = (vec!["a".to_string(), String::new(), "ünïcode".to_string()], Some(-7_i64), vec![Ok::<u8, bool>(1), Err(true)]);
+        assert_eq!(round_trip(&value).unwrap(), value);
+    }
+
+    #[test]
+    fn a_huge_length_fails_without_allocating_it() {
+        // taken at its word, either one would abort on allocation
+        let error = String::read_from(&mut claiming(usize::MAX, b"abc").as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let error = Vec::<u64>::read_from(&mut claiming(usize::MAX, &[0; 20]).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn a_short_string_fails() {
+        let error = String::read_from(&mut claiming(4, b"abc").as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
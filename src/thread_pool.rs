@@ -0,0 +1,282 @@
+use std::{any::Any, collections::VecDeque, marker::PhantomData, num::NonZeroUsize, panic::{self, AssertUnwindSafe}, sync::{mpsc, Arc, Condvar, Mutex, MutexGuard}, thread};
+use crate::executor::Executor;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+struct PoolShared {
+    queue: Mutex<Queue>,
+    job_ready: Condvar,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    closed: bool,
+}
+
+impl PoolShared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// ## A fixed number of worker threads sharing one job queue.
+/// Unlike `map_reduce_async` spawning a thread per chunk, 10,000 jobs are 10,000 entries in the queue,
+/// not 10,000 threads. The threads are started once and reused for every job, [`ThreadPool::spawn`]ed or part of
+/// a [`ThreadPool::scope`], which is how it runs an [`Executor`] batch: a job per task.
+/// - a panicking job is caught, the worker carries on with the next one,
+/// - dropping the pool runs the jobs still queued and then joins the workers.
+///
+/// A scope (and so `execute`) blocks until all of its jobs are done, so opening one from one of the pool's own jobs
+/// can deadlock once every worker is waiting like that.
+pub struct ThreadPool {
+    shared: Arc<PoolShared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl Default for ThreadPool {
+    /// One worker per core.
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+impl ThreadPool {
+
+    pub fn new(threads: usize) -> Self {
+        let shared = Arc::new(PoolShared { queue: Mutex::default(), job_ready: Condvar::new() });
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("pool-worker-{}", i))
+                    .spawn(move || worker(&shared))
+                    .expect("failed to spawn pool worker")
+            })
+            .collect();
+        ThreadPool { shared, workers }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Jobs waiting for a free worker.
+    pub fn queued(&self) -> usize {
+        self.shared.lock().jobs.len()
+    }
+
+    /// Queues a job and returns right away.
+    pub fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        self.push(Box::new(job));
+    }
+
+    /// Runs `f`, whose [`Scope::spawn`]ed jobs may borrow anything that outlives the call, on the pool's workers.
+    /// Doesn't return before every job of the scope is done, also when `f` or one of the jobs panicked,
+    /// the first of those panics is then carried on here.
+    pub fn scope<'env, R>(&self, f: impl FnOnce(&Scope<'_, 'env>) -> R) -> R {
+        let scope = Scope { pool: self, latch: Arc::new(Latch::default()), env: PhantomData };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        let job_panic = scope.latch.wait();
+        match (result, job_panic) {
+            (Err(payload), _) | (Ok(_), Some(payload)) => panic::resume_unwind(payload),
+            (Ok(result), None) => result,
+        }
+    }
+
+    fn push(&self, job: Job) {
+        self.shared.lock().jobs.push_back(job);
+        self.shared.job_ready.notify_one();
+    }
+}
+
+fn worker(shared: &PoolShared) {
+    loop {
+        let job = {
+            let mut queue = shared.lock();
+            loop {
+                if let Some(job) = queue.jobs.pop_front() {
+                    break job;
+                }
+                if queue.closed {
+                    return;
+                }
+                queue = shared.job_ready.wait(queue).unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        };
+        // the panic message is already printed by the panic hook, the worker just keeps going
+        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.job_ready.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+
+/// ## Jobs on a [`ThreadPool`] which may borrow from the caller of [`ThreadPool::scope`].
+/// `'env` is what they may borrow, it's invariant so a job can't sneak in a borrow which ends before the scope does.
+pub struct Scope<'pool, 'env> {
+    pool: &'pool ThreadPool,
+    latch: Arc<Latch>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'env> Scope<'_, 'env> {
+
+    /// Queues a job and returns right away, the scope waits for it.
+    pub fn spawn(&self, job: impl FnOnce() + Send + 'env) {
+        self.latch.add();
+        let latch = Arc::clone(&self.latch);
+        let job: Box<dyn FnOnce() + Send + 'env> = Box::new(move || {
+            // the job and its borrows are gone by the time the scope hears it's done
+            let result = panic::catch_unwind(AssertUnwindSafe(job));
+            latch.done(result.err());
+        });
+        // SAFETY: only the lifetime changes. `ThreadPool::scope` waits on the latch before it returns or unwinds,
+        // and the latch only counts the job as done once it has run and dropped everything it borrowed for 'env.
+        // The pool can't go away in between, the scope borrows it.
+        let job: Job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'env>, Job>(job) };
+        self.pool.push(job);
+    }
+}
+
+/// Counts a scope's unfinished jobs, and keeps the first panic among them.
+#[derive(Default)]
+struct Latch {
+    state: Mutex<(usize, Option<Box<dyn Any + Send>>)>,
+    done: Condvar,
+}
+
+impl Latch {
+
+    fn lock(&self) -> MutexGuard<'_, (usize, Option<Box<dyn Any + Send>>)> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn add(&self) {
+        self.lock().0 += 1;
+    }
+
+    fn done(&self, panic: Option<Box<dyn Any + Send>>) {
+        let mut state = self.lock();
+        state.0 -= 1;
+        if state.1.is_none() {
+            state.1 = panic;
+        }
+        if state.0 == 0 {
+            self.done.notify_all();
+        }
+    }
+
+    /// Blocks until every job is done, with the first panic.
+    fn wait(&self) -> Option<Box<dyn Any + Send>> {
+        let mut state = self.lock();
+        while state.0 > 0 {
+            state = self.done.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        state.1.take()
+    }
+}
+
+
+impl Executor for ThreadPool {
+
+    /// A job per task in a [`ThreadPool::scope`], a panicking task makes `execute` panic once the rest of the batch is done.
+    fn execute<T, R, F>(&self, tasks: Vec<T>, task: F) -> Vec<R> where T: Send, R: Send, F: Fn(T) -> R + Sync {
+        let (sender, receiver) = mpsc::channel();
+        let task = &task;
+        self.scope(|scope| {
+            for (index, next) in tasks.into_iter().enumerate() {
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let _ = sender.send((index, task(next)));
+                });
+            }
+        });
+        drop(sender);
+
+        let mut results = receiver.into_iter().collect::<Vec<_>>();
+        results.sort_unstable_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+    use super::*;
+
+    #[test]
+    fn spawned_jobs_survive_a_panicking_one() {
+        let pool = ThreadPool::new(2);
+        let (sender, receiver) = mpsc::channel();
+        pool.spawn(|| panic!("a job which panics"));
+        for job in 0..10 {
+            let sender = sender.clone();
+            pool.spawn(move || sender.send(job).unwrap());
+        }
+        drop(pool);
+        let mut done = receiver.try_iter().collect::<Vec<_>>();
+        done.sort();
+        assert_eq!(done, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn a_batch_borrows_and_keeps_its_order() {
+        let pool = ThreadPool::new(3);
+        let words = ["zero", "one", "two", "three", "four", "five", "six"].map(String::from);
+        let suffix = String::from("!");
+        let shouted = pool.execute(words.iter().collect(), |word| format!("{}{}", word, suffix));
+        assert_eq!(shouted, words.iter().map(|word| format!("{}!", word)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn a_batch_runs_on_the_pools_own_workers() {
+        let pool = ThreadPool::new(3);
+        let names = pool.execute((0..50).collect(), |_: usize| thread::current().name().map(String::from));
+        let names = names.into_iter().collect::<Option<BTreeSet<_>>>().expect("pool workers have names");
+        assert!(!names.is_empty() && names.iter().all(|name| name.starts_with("pool-worker-")), "{:?}", names);
+    }
+
+    #[test]
+    fn a_scope_waits_for_its_jobs_when_it_panics() {
+        let pool = ThreadPool::new(2);
+        let ran = AtomicUsize::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|scope| {
+                for _ in 0..4 {
+                    scope.spawn(|| {
+                        thread::sleep(Duration::from_millis(5));
+                        ran.fetch_add(1, Ordering::SeqCst);
+                    });
+                }
+                panic!("the scope itself");
+            })
+        }));
+        assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"the scope itself"));
+        assert_eq!(ran.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn a_panicking_task_panics_after_the_rest_ran() {
+        let pool = ThreadPool::new(2);
+        let ran = AtomicUsize::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.execute((0..20).collect(), |task: usize| {
+                if task == 3 {
+                    panic!("task 3");
+                }
+                ran.fetch_add(1, Ordering::SeqCst);
+            })
+        }));
+        assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"task 3"));
+        assert_eq!(ran.load(Ordering::SeqCst), 19);
+    }
+}
@@ -0,0 +1,323 @@
+use std::{collections::VecDeque, sync::{atomic::{AtomicU64, Ordering}, Arc, Condvar, Mutex, MutexGuard}, time::Instant};
+use tokio::sync::Notify;
+use crate::clock::{Clock, RealClock};
+
+/// What every subscriber of a [`TickSource`] receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    /// Counts up from 0 for the whole source, a subscriber attaching late starts at a higher index.
+    pub index: u64,
+    pub at: Instant,
+}
+
+/// ### What happens when a subscriber's buffer is full and the next tick comes in.
+/// - `DropOldest`: throw away the oldest buffered tick to make room, the subscriber can see how many with [`TickSubscriber::dropped`].
+/// - `Block`: the publisher waits until the subscriber made room, this holds up the ticker (and every other subscriber),
+///   so only use it with a thread backed ticker, never inside a tokio task.
+/// - `Disconnect`: the slow subscriber is detached, it can still drain what it has buffered and then `recv()` returns `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    DropOldest,
+    Block,
+    Disconnect,
+}
+
+
+/// ## One clock shared by many components.
+/// `ticker_mpsc_external` sends `()` into a single `mpsc::Sender` so only one consumer can listen,
+/// a `TickSource` fans every tick out to any number of [`TickSubscriber`]s, which can attach and detach at runtime.
+///
+/// The source doesn't tick by itself, it's fed by a [`Ticker`](crate::ticker::Ticker) running [`TickSource::publisher`]:
+/// ```ignore
+/// let source = TickSource::new();
+/// let fast = source.subscribe(16, LagPolicy::DropOldest);
+/// let handle = Ticker::new(Duration::from_secs(1), source.publisher()).start();
+/// while let Some(tick) = fast.recv() { ... }
+/// ```
+/// Once the source and all of its publishers are dropped the subscribers drain their buffers and then get `None`.
+#[derive(Clone)]
+pub struct TickSource {
+    inner: Arc<SourceInner>,
+}
+
+struct SourceInner {
+    clock: Arc<dyn Clock>,
+    next_index: AtomicU64,
+    subscribers: Mutex<Vec<Arc<Slot>>>,
+}
+
+/// The buffer between the publisher and one subscriber.
+struct Slot {
+    state: Mutex<SlotState>,
+    policy: LagPolicy,
+    // signalled when a tick arrives, the subscriber goes away or the source closes
+    readable: Condvar,
+    // signalled when the subscriber made room, for `LagPolicy::Block`
+    writable: Condvar,
+    notify: Notify,
+}
+
+struct SlotState {
+    buffer: VecDeque<Tick>,
+    capacity: usize,
+    dropped: u64,
+    // the subscriber dropped its end
+    detached: bool,
+    // no more ticks will come: lagged behind with `LagPolicy::Disconnect`, or the source is gone
+    closed: bool,
+}
+
+impl Slot {
+
+    fn lock(&self) -> MutexGuard<'_, SlotState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn wake(&self) {
+        self.readable.notify_all();
+        self.writable.notify_all();
+        self.notify.notify_one();
+    }
+
+    /// `false` once the slot should be removed from the source.
+    fn deliver(&self, tick: Tick) -> bool {
+
+        let mut state = self.lock();
+
+        while state.buffer.len() >= state.capacity && !state.detached {
+            match self.policy {
+                LagPolicy::DropOldest => {
+                    state.buffer.pop_front();
+                    state.dropped += 1;
+                }
+                LagPolicy::Block => {
+                    state = self.writable.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+                }
+                LagPolicy::Disconnect => {
+                    state.closed = true;
+                    drop(state);
+                    self.wake();
+                    return false;
+                }
+            }
+        }
+
+        if state.detached {
+            return false;
+        }
+
+        state.buffer.push_back(tick);
+        drop(state);
+        self.wake();
+        true
+    }
+}
+
+impl Default for TickSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TickSource {
+
+    pub fn new() -> Self {
+        Self::with_clock(RealClock::default())
+    }
+
+    /// Tick timestamps are read from `clock`, which should be the same one the feeding ticker runs on.
+    pub fn with_clock(clock: impl Clock) -> Self {
+        TickSource {
+            inner: Arc::new(SourceInner {
+                clock: Arc::new(clock),
+                next_index: AtomicU64::new(0),
+                subscribers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Attaches a new subscriber buffering at most `capacity` ticks (at least 1), it only sees ticks published from now on.
+    pub fn subscribe(&self, capacity: usize, policy: LagPolicy) -> TickSubscriber {
+        let slot = Arc::new(Slot {
+            state: Mutex::new(SlotState {
+                buffer: VecDeque::with_capacity(capacity.max(1)),
+                capacity: capacity.max(1),
+                dropped: 0,
+                detached: false,
+                closed: false,
+            }),
+            policy,
+            readable: Condvar::new(),
+            writable: Condvar::new(),
+            notify: Notify::new(),
+        });
+        self.inner.lock_subscribers().push(Arc::clone(&slot));
+        TickSubscriber { slot }
+    }
+
+    /// Number of subscribers currently attached.
+    pub fn subscribers(&self) -> usize {
+        self.inner.lock_subscribers().iter().filter(|slot| !slot.lock().detached).count()
+    }
+
+    /// Stamps the next tick and hands it to every subscriber, returns the tick.
+    pub fn publish(&self) -> Tick {
+
+        let tick = Tick {
+            index: self.inner.next_index.fetch_add(1, Ordering::SeqCst),
+            at: self.inner.clock.now(),
+        };
+
+        // deliver without holding the list, a blocking subscriber mustn't keep others from (un)subscribing
+        let subscribers = self.inner.lock_subscribers().clone();
+        let gone = subscribers.iter().filter(|slot| !slot.deliver(tick)).collect::<Vec<_>>();
+
+        if !gone.is_empty() {
+            self.inner.lock_subscribers().retain(|slot| !gone.iter().any(|gone| Arc::ptr_eq(slot, gone)));
+        }
+
+        tick
+    }
+
+    /// The closure to hand to a [`Ticker`](crate::ticker::Ticker), it publishes one tick per run.
+    pub fn publisher(&self) -> impl FnMut() + Send + 'static {
+        let source = self.clone();
+        move || {
+            source.publish();
+        }
+    }
+}
+
+impl SourceInner {
+    fn lock_subscribers(&self) -> MutexGuard<'_, Vec<Arc<Slot>>> {
+        self.subscribers.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for SourceInner {
+    fn drop(&mut self) {
+        for slot in self.lock_subscribers().drain(..) {
+            slot.lock().closed = true;
+            slot.wake();
+        }
+    }
+}
+
+
+/// Receiving end of a [`TickSource`], dropping it detaches from the source.
+pub struct TickSubscriber {
+    slot: Arc<Slot>,
+}
+
+impl TickSubscriber {
+
+    /// Blocks until the next tick, `None` once the subscriber got disconnected or the source is gone.
+    pub fn recv(&self) -> Option<Tick> {
+        let mut state = self.slot.lock();
+        loop {
+            if let Some(tick) = state.buffer.pop_front() {
+                drop(state);
+                self.slot.writable.notify_all();
+                return Some(tick);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.slot.readable.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<Tick> {
+        let tick = self.slot.lock().buffer.pop_front();
+        if tick.is_some() {
+            self.slot.writable.notify_all();
+        }
+        tick
+    }
+
+    /// Same as [`TickSubscriber::recv`] for async code.
+    pub async fn recv_async(&self) -> Option<Tick> {
+        loop {
+            // `notify_one` keeps a permit, so a tick delivered right after the check isn't missed
+            if let Some(tick) = self.try_recv() {
+                return Some(tick);
+            }
+            if self.slot.lock().closed {
+                return None;
+            }
+            self.slot.notify.notified().await;
+        }
+    }
+
+    /// Ticks thrown away because of [`LagPolicy::DropOldest`].
+    pub fn dropped(&self) -> u64 {
+        self.slot.lock().dropped
+    }
+
+    /// `true` once no more ticks will arrive, buffered ones can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.slot.lock().closed
+    }
+}
+
+impl Drop for TickSubscriber {
+    fn drop(&mut self) {
+        self.slot.lock().detached = true;
+        // unblocks a publisher waiting on us with `LagPolicy::Block`
+        self.slot.wake();
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{clock::ManualClock, ticker::Ticker};
+    use super::*;
+
+    /// One ticker feeding three listeners with different lag policies, one of which detaches half way through.
+    #[test]
+    fn listeners_with_different_lag_policies() {
+        let clock = ManualClock::new();
+        let source = TickSource::with_clock(clock.clone());
+
+        let steady = source.subscribe(4, LagPolicy::Block);
+        let sleepy = source.subscribe(2, LagPolicy::DropOldest);
+        let stuck = source.subscribe(2, LagPolicy::Disconnect);
+
+        let ticker = Ticker::new(Duration::from_millis(50), source.publisher()).clock(clock.clone()).start();
+        clock.wait_for_idle(1);
+
+        // the steady one reads after every tick and gets them all, the others never read
+        let mut received = Vec::new();
+        for _ in 1..10 {
+            received.extend(std::iter::from_fn(|| steady.try_recv()).map(|tick| tick.index));
+            clock.advance(Duration::from_millis(50));
+            clock.wait_for_idle(1);
+        }
+        received.extend(std::iter::from_fn(|| steady.try_recv()).map(|tick| tick.index));
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+
+        // the sleepy one finds the newest 2 ticks, the rest were dropped
+        assert_eq!(std::iter::from_fn(|| sleepy.try_recv()).map(|tick| tick.index).collect::<Vec<_>>(), [8, 9]);
+        assert_eq!(sleepy.dropped(), 8);
+
+        // the stuck one got kicked out after its buffer of 2 filled up
+        assert!(stuck.is_disconnected());
+        assert_eq!(std::iter::from_fn(|| stuck.recv()).map(|tick| tick.index).collect::<Vec<_>>(), [0, 1]);
+
+        // a late subscriber only sees ticks from now on
+        drop(steady);
+        drop(sleepy);
+        let late = source.subscribe(1, LagPolicy::DropOldest);
+        clock.advance(Duration::from_millis(50));
+        clock.wait_for_idle(1);
+        assert_eq!(late.try_recv().map(|tick| tick.index), Some(10));
+        // the steady and the sleepy listener are gone, the stuck one was kicked out
+        assert_eq!(source.subscribers(), 1);
+
+        ticker.stop();
+        ticker.join().unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use std::{pin::Pin, sync::{atomic::{AtomicU64, Ordering}, Arc}, task::{Context, Poll}, time::Duration};
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use crate::{tick_source::Tick, ticker::{Ticker, TickerHandle}};
+
+/// ## Ticks as a `Stream`, for async code which would rather pull than hand over a closure.
+/// ```ignore
+/// let mut ticks = TickStream::new(Duration::from_secs(1));
+/// while let Some(tick) = ticks.next().await { ... }
+/// ```
+/// It's the usual [`Ticker`] running as a tokio task underneath, so schedules, missed tick behavior, clocks and
+/// cancel tokens all work the same, set them up on the ticker and finish with [`Ticker::into_stream`].
+/// It goes along with `tokio_stream::StreamExt` (`take(n)`, `timeout(d)`, ...) and `tokio::select!`.
+///
+/// Dropping the stream stops the ticker, the stream ends once the ticker does (stopped, cancelled, or a cron schedule ran out).
+pub struct TickStream {
+    receiver: mpsc::Receiver<Tick>,
+    handle: TickerHandle,
+    dropped: Arc<AtomicU64>,
+}
+
+impl TickStream {
+
+    /// Ticks every `interval`, the first one right away.
+    pub fn new(interval: Duration) -> Self {
+        Ticker::new(interval, || {}).into_stream(1)
+    }
+
+    /// The ticker underneath, to pause, resume or read its metrics.
+    pub fn handle(&self) -> &TickerHandle {
+        &self.handle
+    }
+
+    /// Ticks which came while the buffer was full because nobody was polling.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+}
+
+impl Stream for TickStream {
+    type Item = Tick;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Tick>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for TickStream {
+    fn drop(&mut self) {
+        self.handle.stop();
+    }
+}
+
+impl<F> Ticker<F> where F: FnMut() + Send + 'static, {
+
+    /// Starts the ticker as a tokio task and hands its ticks out as a [`TickStream`], the closure still runs first on every tick.
+    /// Up to `capacity` ticks (at least 1) are buffered for a consumer which is busy elsewhere,
+    /// further ones are dropped and counted in [`TickStream::dropped`], like `MissedTickBehavior::Skip` does.
+    pub fn into_stream(self, capacity: usize) -> TickStream {
+
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let dropped_clone = Arc::clone(&dropped);
+
+        let clock = self.shared_clock();
+        let mut index = 0;
+
+        let handle = self.map_func(|mut func| move || {
+            func();
+            let tick = Tick { index, at: clock.now() };
+            index += 1;
+            if let Err(mpsc::error::TrySendError::Full(_)) = sender.try_send(tick) {
+                dropped_clone.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .start_async();
+
+        TickStream { receiver, handle, dropped }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+    use crate::clock::{Clock, ManualClock};
+    use super::*;
+
+    /// A tick stream driven by `take` and `select!`, overflowing its buffer, then dropped half way through.
+    #[tokio::test]
+    async fn ticks_as_a_stream() {
+        let clock = ManualClock::new();
+        let runs = Arc::new(AtomicU64::new(0));
+        let runs_clone = Arc::clone(&runs);
+        let mut ticks = Ticker::new(Duration::from_millis(100), move || {
+            runs_clone.fetch_add(1, Ordering::SeqCst);
+        })
+        .clock(clock.clone())
+        .into_stream(3);
+
+        // the first one comes right away, the next two wait in the buffer
+        clock.idle(1).await;
+        for _ in 0..2 {
+            clock.advance(Duration::from_millis(100));
+            clock.idle(1).await;
+        }
+
+        // the first 3, `take` borrows the stream so it keeps going afterwards
+        let first = (&mut ticks).take(3).map(|tick| tick.index).collect::<Vec<_>>().await;
+        assert_eq!(first, [0, 1, 2]);
+
+        // nobody polls for 4 ticks, the last one doesn't fit
+        for _ in 0..4 {
+            clock.advance(Duration::from_millis(100));
+            clock.idle(1).await;
+        }
+        assert_eq!(ticks.dropped(), 1);
+        assert_eq!((&mut ticks).take(3).map(|tick| tick.index).collect::<Vec<_>>().await, [3, 4, 5]);
+
+        // racing the ticks against something else, a deadline before the next tick wins
+        let deadline = clock.sleep_async(Duration::from_millis(50));
+        tokio::pin!(deadline);
+        clock.advance(Duration::from_millis(50));
+        tokio::select! {
+            biased;
+            tick = ticks.next() => panic!("the next tick is 50ms away, got {:?}", tick),
+            _ = &mut deadline => {}
+        }
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(ticks.next().await.map(|tick| tick.index), Some(7));
+
+        // dropping the stream stops its ticker, which then stops sleeping on the clock
+        clock.idle(1).await;
+        drop(ticks);
+        while clock.idle_sleepers() > 0 {
+            tokio::task::yield_now().await;
+        }
+        let runs_at_drop = runs.load(Ordering::SeqCst);
+        clock.advance(Duration::from_millis(300));
+        tokio::task::yield_now().await;
+        assert_eq!(runs.load(Ordering::SeqCst), runs_at_drop);
+    }
+}
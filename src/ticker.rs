@@ -1,12 +1,17 @@
 use tokio::time::{self, sleep};
-use std::{sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, mpsc::{self, Receiver, Sender}, Arc, Mutex}, thread, time::Duration};
+use std::{collections::VecDeque, panic::{self, AssertUnwindSafe}, sync::{atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering}, mpsc::{self, Receiver, Sender}, Arc, Mutex}, thread, time::{Duration, Instant}};
 use tokio::sync::Notify;
+use crate::clock::{Clock, RealClock};
+use crate::metrics::TickerMetrics;
+use crate::schedule::Schedule;
+use crate::shutdown::{poll_finished, CancellationToken, OnCancel, Shutdown};
 
 
-fn ticker<F>(mut func: F) where F: FnMut() + Send + 'static, {
-    loop {
-        func();
-        thread::sleep(Duration::from_secs(1));
+/// Blocks the calling thread, ticking every second until `token` is cancelled.
+fn ticker<F>(func: F, token: CancellationToken) where F: FnMut() + Send + 'static, {
+    let handle = Ticker::new(Duration::from_secs(1), func).cancel_token(token).start();
+    if let Err(payload) = handle.join() {
+        panic::resume_unwind(payload);
     }
 }
 
@@ -14,18 +19,15 @@ fn ticker<F>(mut func: F) where F: FnMut() + Send + 'static, {
 /// ### The `tokio::select!` macro allows waiting on multiple async computations and returns when a single computation completes.
 pub async fn async_ticker_with_notification_mechanism(signal: Arc<Notify>) {
     let mut counter = 0;
-    loop {
-        tokio::select! {
-            _ = signal.notified() => {
-                println!("Received shutdown signal, stopping ticker...");
-                break;
-            }
-            _ = sleep(Duration::from_secs(1)) => {
-                println!("Ticker executing. Counter: {}", counter);
-                counter += 1;
-            } 
-        }
-    }
+    let handle = Ticker::new(Duration::from_secs(1), move || {
+        println!("Ticker executing. Counter: {}", counter);
+        counter += 1;
+    }).start_async();
+
+    signal.notified().await;
+    println!("Received shutdown signal, stopping ticker...");
+    handle.stop();
+    handle.join_async().await.unwrap();
 }
 
 
@@ -49,32 +51,21 @@ pub async fn async_ticker_with_notification_mechanism_main() {
 async fn _ticker_async_with_atomic_and_stop() {
     
     let counter = Arc::new(AtomicUsize::new(0));
-    let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
-
     let counter_clone = Arc::clone(&counter);
-    let running_clone = Arc::clone(&running);
-
-    let handle = tokio::spawn(async move {
 
-        while running_clone.load(Ordering::SeqCst) {
-
-            let current_count = counter_clone.fetch_add(1, Ordering::SeqCst);
-            println!("Ticker executing. Counter: {}", current_count + 1); // Print the incremented value
-
-            sleep(Duration::from_secs(1)).await;
-        }
-
-        println!("Ticker task stopped.");
-    });
+    let handle = Ticker::new(Duration::from_secs(1), move || {
+        let current_count = counter_clone.fetch_add(1, Ordering::SeqCst);
+        println!("Ticker executing. Counter: {}", current_count + 1); // Print the incremented value
+    }).start_async();
 
     sleep(Duration::from_secs(5)).await;
     println!("Main task doing some work...");
     sleep(Duration::from_secs(3)).await;
 
     println!("Stopping the ticker...");
-    running.store(false, Ordering::SeqCst);
+    handle.stop();
 
-    handle.await.unwrap();
+    handle.join_async().await.unwrap();
     println!("Ticker stopped. Main task continues.");
 }
 
@@ -82,23 +73,15 @@ async fn _ticker_async_with_atomic_and_stop() {
 pub async fn ticker_async_with_mutex_and_stop() {
 
     let counter = Arc::new(Mutex::new(0));
-    let stop_bool = Arc::new(AtomicBool::new(false));
-
     let counter_clone = Arc::clone(&counter);
-    let stop_bool_clone = Arc::clone(&stop_bool);
-
-    let handle = tokio::spawn(async move {
-        while !stop_bool_clone.load(Ordering::SeqCst) {
-            { 
-                // critical section
-                let mut count = counter_clone.lock().unwrap(); // Acquire the lock
-                println!("Ticker executing the closure. Counter: {}", *count);
-                *count += 1;
-            } 
-            // Lock released here
-            sleep(Duration::from_secs(1)).await; // Asynchronous sleep
-        }
-    });
+
+    let handle = Ticker::new(Duration::from_secs(1), move || {
+        // critical section
+        let mut count = counter_clone.lock().unwrap(); // Acquire the lock
+        println!("Ticker executing the closure. Counter: {}", *count);
+        *count += 1;
+        // Lock released here
+    }).start_async();
 
     // the main task running (otherwise, the spawned task will be terminated) or else join() the handle.
     // loop {
@@ -112,41 +95,34 @@ pub async fn ticker_async_with_mutex_and_stop() {
 
     // Stop the ticker task
     println!("Stopping the ticker...");
-    stop_bool.store(true, Ordering::SeqCst);
+    handle.stop();
 
-    // handle.await.unwrap();
+    // wait for the task to stop, otherwise "stopped" is printed while it may still be ticking
+    handle.join_async().await.unwrap();
     println!("Ticker stopped. Main task continues.");
 
 }
 
 
-fn ticker_mpsc<F>(mut func: F) where F: FnMut() + Send + 'static, {
+fn ticker_mpsc<F>(mut func: F, token: CancellationToken) where F: FnMut() + Send + 'static, {
     
     let (tx, rx) = mpsc::channel();
 
     // Spawn a separate thread to act as a timer
-    thread::spawn(move || {
-        loop {
-            tx.send(()).unwrap(); // Send a tick signal
-            thread::sleep(Duration::from_secs(1)); // Avoid blocking the main thread
-        }
-    });
+    ticker_mpsc_external(tx, token);
 
-    // Main ticker loop reacting to tick events
+    // Main ticker loop reacting to tick events, ends once the timer thread dropped its `tx`
     for _ in rx {
         func();
     }
 }
 
 
-fn ticker_mpsc_external(tx: Sender<()>) {
-    // Spawn a separate thread to act as a timer
-    thread::spawn(move || {
-        loop {
-            tx.send(()).unwrap(); // Send a tick signal
-            thread::sleep(Duration::from_secs(1)); // Avoid blocking the main thread
-        }
-    });
+fn ticker_mpsc_external(tx: Sender<()>, token: CancellationToken) {
+    // A ticker thread acts as the timer, it stops on cancel and drops `tx` with its closure
+    Ticker::new(Duration::from_secs(1), move || {
+        let _ = tx.send(());
+    }).cancel_token(token).start();
 }
 
 
@@ -154,8 +130,9 @@ pub fn ticker_mpsc_external_main() {
 
     let mut counter = 0;
     let (tx, rx) = mpsc::channel();
+    let token = CancellationToken::new();
 
-    ticker_mpsc_external(tx);
+    ticker_mpsc_external(tx, token.clone());
 
     let lt = thread::spawn(move || {
         println!("I the thread who listens to timer threads");
@@ -166,6 +143,9 @@ pub fn ticker_mpsc_external_main() {
     });
 
     println!("IM THE MAIN THREAD !!");
+
+    thread::sleep(Duration::from_secs(5));
+    token.cancel();
     
     lt.join().unwrap();
 }
@@ -174,29 +154,35 @@ pub fn ticker_mpsc_external_main() {
 pub fn ticker_mpsc_main() {
 
     let mut counter = 0;
+    let mut shutdown = Shutdown::new();
 
+    let token = shutdown.token();
     let handle1 = thread::spawn(move || {
         ticker_mpsc(move || {
             println!("Ticker executing the closure of the main function. Counter: {}", counter);
             counter += 1;
-        });
+        }, token);
     });
 
     println!("I'm the main thread!");
 
-    let handle2 = thread::spawn(|| {
-        loop {
+    let token = shutdown.token();
+    let handle2 = thread::spawn(move || {
+        while !token.is_cancelled() {
             println!("I am another thread!!");
-            thread::sleep(Duration::from_secs(1)); // This part can also be removed with async
+            token.wait_timeout(Duration::from_secs(1)); // This part can also be removed with async
         }
     });
 
-    println!("\n Main thread: JOINIG BOTH THREADS ! \n");
+    shutdown.register_thread("ticker", handle1);
+    shutdown.register_thread("another thread", handle2);
+
+    thread::sleep(Duration::from_secs(5));
+    println!("\n Main thread: STOPPING BOTH THREADS ! \n");
 
-    handle1.join().unwrap();
-    handle2.join().unwrap();
+    let report = shutdown.shutdown_blocking(Duration::from_secs(2));
 
-    println!("\n Main thread: BOTH THREADS JOINED \n");
+    println!("\n Main thread: BOTH THREADS JOINED, stopped cleanly: {:?} \n", report.stopped());
 
 }
 
@@ -204,27 +190,934 @@ pub fn ticker_mpsc_main() {
 pub async fn ticker_main() {
 
     let mut counter = 0;
+    let mut shutdown = Shutdown::new();
 
+    let token = shutdown.token();
     let handle1 = thread::spawn(move || {
         ticker(move || {
             println!("Ticker executing the closure of the main function. Counter: {}", counter);
             counter += 1;
-        });
+        }, token);
     });
 
     println!("I'm the main thread!");
 
-    let handle2 = thread::spawn(|| {
-        loop {
+    let token = shutdown.token();
+    let handle2 = thread::spawn(move || {
+        while !token.is_cancelled() {
             println!("I am another thread!!");
-            thread::sleep(Duration::from_secs(1));
+            token.wait_timeout(Duration::from_secs(1));
         }
     });
 
-    // Join threads to prevent the main thread from exiting early
-    handle1.join().expect("Ticker thread panicked");
-    handle2.join().expect("Another thread panicked");
+    shutdown.register_thread("ticker", handle1);
+    shutdown.register_thread("another thread", handle2);
+
+    sleep(Duration::from_secs(5)).await;
+
+    // Stop and join threads, a thread which doesn't make it within 2 secs is reported as aborted
+    let report = shutdown.shutdown(Duration::from_secs(2)).await;
+
+    println!("Main thread got over! {:?}", report);
+
+}
+
+/// ## One reusable ticker, the variants above are thin wrappers over it.
+/// `Ticker` runs `func` right away and then once every `interval`, either on its own std thread
+/// ([`Ticker::start`]) or as a tokio task ([`Ticker::start_async`]).
+/// [`Ticker::with_schedule`] takes a cron expression instead, e.g. `"0 3 * * *"` for a nightly job.
+/// Both give back a [`TickerHandle`] which can `pause()`, `resume()`, `stop()` and `join()` the ticker.
+/// 
+/// ```ignore
+/// let handle = Ticker::new(Duration::from_millis(500), || println!("tick")).start();
+/// handle.pause();
+/// handle.resume();
+/// handle.stop();
+/// handle.join().unwrap();
+/// ```
+pub struct Ticker<F> {
+    schedule: Schedule,
+    func: F,
+    mode: TickMode,
+    missed_tick_behavior: MissedTickBehavior,
+    clock: Arc<dyn Clock>,
+    cancel_token: Option<CancellationToken>,
+    supervision: Option<Supervision>,
+    start_delay: Duration,
+}
+
+impl<F> Ticker<F> where F: FnMut() + Send + 'static, {
+
+    pub fn new(interval: Duration, func: F) -> Self {
+        Self::with_schedule(Schedule::every(interval), func)
+    }
+
+    /// A cron schedule first fires at its next match, not right away, and the ticker exits once
+    /// the schedule has no more matches.
+    pub fn with_schedule(schedule: Schedule, func: F) -> Self {
+        Ticker {
+            schedule,
+            func,
+            mode: TickMode::default(),
+            missed_tick_behavior: MissedTickBehavior::default(),
+            clock: Arc::new(RealClock::default()),
+            cancel_token: None,
+            supervision: None,
+            start_delay: Duration::ZERO,
+        }
+    }
+
+    pub fn mode(mut self, mode: TickMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Only used in [`TickMode::FixedRate`], a fixed-delay ticker can never fall behind.
+    pub fn missed_tick_behavior(mut self, behavior: MissedTickBehavior) -> Self {
+        self.missed_tick_behavior = behavior;
+        self
+    }
+
+    /// Swap the wall clock for e.g. a [`ManualClock`](crate::clock::ManualClock) to step through ticks by hand.
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Cancelling `token` stops the ticker, same as calling `stop()` on its handle.
+    pub fn cancel_token(mut self, token: CancellationToken) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Holds the first tick back by `delay`, an interval ticker then continues every interval from there,
+    /// a cron ticker waits for its first match after the delay.
+    pub fn start_delay(mut self, delay: Duration) -> Self {
+        self.start_delay = delay;
+        self
+    }
+
+    /// Catch panics of the closure instead of letting them kill the ticker, see [`Supervision`].
+    pub fn supervise(mut self, supervision: Supervision) -> Self {
+        self.supervision = Some(supervision);
+        self
+    }
+
+    pub(crate) fn shared_clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&self.clock)
+    }
+
+    /// Same ticker running `map(func)` instead, lets e.g. [`Ticker::into_stream`] add its own work to every tick.
+    pub(crate) fn map_func<G>(self, map: impl FnOnce(F) -> G) -> Ticker<G> {
+        Ticker {
+            schedule: self.schedule,
+            func: map(self.func),
+            mode: self.mode,
+            missed_tick_behavior: self.missed_tick_behavior,
+            clock: self.clock,
+            cancel_token: self.cancel_token,
+            supervision: self.supervision,
+            start_delay: self.start_delay,
+        }
+    }
+
+    /// The ticker's state, and the callback which stops it when its token is cancelled.
+    /// The ticker loop keeps the callback registered until it exits.
+    fn state(&self) -> (Arc<TickerState>, Option<OnCancel>) {
+        let state = Arc::new(TickerState::new(Arc::clone(&self.clock)));
+        let on_cancel = self.cancel_token.as_ref().map(|token| {
+            let state = Arc::downgrade(&state);
+            token.on_cancel(move || {
+                if let Some(state) = state.upgrade() {
+                    state.update(|control| control.stopped = true);
+                }
+            })
+        });
+        (state, on_cancel)
+    }
+
+    /// Runs the ticker loop on a dedicated std thread.
+    pub fn start(self) -> TickerHandle {
+        let (state, on_cancel) = self.state();
+        let state_clone = Arc::clone(&state);
+        let handle = thread::spawn(move || {
+            let _on_cancel = on_cancel;
+            run_thread_ticker(self, state_clone)
+        });
+        TickerHandle { state, worker: TickerWorker::Thread(handle) }
+    }
+
+    /// Runs the ticker loop as a tokio task, must be called from inside a tokio runtime.
+    pub fn start_async(self) -> TickerHandle {
+        let (state, on_cancel) = self.state();
+        let state_clone = Arc::clone(&state);
+        let handle = tokio::spawn(async move {
+            let _on_cancel = on_cancel;
+            run_async_ticker(self, state_clone).await
+        });
+        TickerHandle { state, worker: TickerWorker::Task(handle, tokio::runtime::Handle::current()) }
+    }
+}
+
 
-    println!("Main thread got over!");
+/// ### How the next tick is scheduled relative to the previous one.
+/// - `FixedRate`: ticks are due at `start + n * interval`, the time the closure takes does not shift them.
+/// - `FixedDelay`: the next tick is due `interval` after the closure returned (what `sleep(1s)` after the work does),
+///   so the period drifts by however long the work takes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TickMode {
+    #[default]
+    FixedRate,
+    FixedDelay,
+}
+
+/// ### What a fixed-rate ticker does when the closure ran past one or more due ticks.
+/// Named after [`tokio::time::MissedTickBehavior`] which behaves the same way.
+/// - `Burst`: run the missed ticks back to back until caught up, then continue on the original schedule.
+/// - `Delay`: run one tick right away and restart the schedule from there.
+/// - `Skip`: drop the missed ticks and wait for the next one on the original schedule.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    #[default]
+    Burst,
+    Delay,
+    Skip,
+}
+
+/// When the tick after the one `scheduled` for is due, given the closure returned at `now`.
+/// `None` once the schedule has nothing left to fire.
+fn next_deadline(mode: TickMode, behavior: MissedTickBehavior, schedule: &Schedule, clock: &dyn Clock, scheduled: Instant, now: Instant) -> Option<Instant> {
+
+    if mode == TickMode::FixedDelay {
+        return schedule.next_instant_after(now, clock);
+    }
+
+    let next = schedule.next_instant_after(scheduled, clock)?;
+    if next > now {
+        return Some(next);
+    }
+
+    match behavior {
+        MissedTickBehavior::Burst => Some(next),
+        MissedTickBehavior::Delay => Some(now),
+        MissedTickBehavior::Skip => match schedule.interval() {
+            // first deadline of the original grid which is still in the future, in `u128` nanoseconds
+            // since a long stall on a short interval can be more missed ticks than any `u32` counts
+            Some(interval) => {
+                let interval = interval.as_nanos().max(1);
+                let offset = ((now - scheduled).as_nanos() / interval + 1) * interval;
+                let offset = Duration::new(u64::try_from(offset / 1_000_000_000).ok()?, (offset % 1_000_000_000) as u32);
+                scheduled.checked_add(offset).or_else(|| schedule.next_instant_after(now, clock))
+            }
+            None => schedule.next_instant_after(now, clock),
+        },
+    }
+}
+
+
+/// ## What a supervised ticker does when its closure panics.
+/// Without supervision a panic kills the ticker thread or task just like in `ticker()` / `ticker_mpsc()`.
+/// A supervised ticker catches the panic of every tick, records it (see [`TickerHandle::failures`]) and carries on:
+/// - the next tick is held back by a backoff, which doubles with every consecutive failure up to `max_backoff`
+///   and resets once a tick succeeds,
+/// - after `max_failures` consecutive failures it gives up, either stopping quietly ([`GiveUp::Stop`])
+///   or re-raising the last panic ([`GiveUp::Escalate`]) so `join()` / a [`Shutdown`] report sees it.
+///
+/// ```ignore
+/// let supervision = Supervision::restart()
+///     .backoff(Duration::from_secs(1), Duration::from_secs(60))
+///     .max_failures(5, GiveUp::Escalate);
+/// Ticker::new(interval, job).supervise(supervision).start();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Supervision {
+    backoff: Duration,
+    max_backoff: Duration,
+    max_failures: Option<u32>,
+    on_max_failures: GiveUp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GiveUp {
+    Stop,
+    Escalate,
+}
+
+impl Supervision {
+
+    /// Carry on with the next tick as if nothing happened, forever.
+    pub fn restart() -> Self {
+        Supervision { backoff: Duration::ZERO, max_backoff: Duration::ZERO, max_failures: None, on_max_failures: GiveUp::Stop }
+    }
+
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    pub fn max_failures(mut self, failures: u32, then: GiveUp) -> Self {
+        self.max_failures = Some(failures.max(1));
+        self.on_max_failures = then;
+        self
+    }
+
+    /// `backoff * 2^(consecutive - 1)`, capped at `max_backoff`.
+    fn backoff_after(&self, consecutive: u32) -> Duration {
+        let factor = 2u32.saturating_pow(consecutive.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// One caught panic of a supervised ticker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickFailure {
+    /// Index of the tick, counting from 0 like [`TickerHandle::ticks`].
+    pub tick: u64,
+    pub at: Instant,
+    pub message: String,
+}
+
+/// How many failures a handle remembers, the total is in [`TickerHandle::failure_count`].
+const KEPT_FAILURES: usize = 32;
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+struct TickerControl {
+    paused: bool,
+    stopped: bool,
+}
+
+/// State shared between a [`TickerHandle`] and its running loop.
+/// - a sleeping ticker thread is woken through [`Clock::interrupt`],
+/// - a sleeping ticker task through the `Notify` (`notify_one` keeps a permit so no wake up gets lost).
+struct TickerState {
+    control: Mutex<TickerControl>,
+    clock: Arc<dyn Clock>,
+    notify: Notify,
+    ticks: AtomicU64,
+    consecutive_failures: AtomicU32,
+    failure_count: AtomicU64,
+    failures: Mutex<VecDeque<TickFailure>>,
+    // `ticks` and `failures` are kept in the atomics above and filled in by `TickerHandle::metrics`
+    metrics: Mutex<TickerMetrics>,
+}
 
-}
\ No newline at end of file
+impl TickerState {
+
+    fn new(clock: Arc<dyn Clock>) -> Self {
+        TickerState {
+            control: Mutex::new(TickerControl::default()),
+            clock,
+            notify: Notify::new(),
+            ticks: AtomicU64::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            failure_count: AtomicU64::new(0),
+            failures: Mutex::new(VecDeque::new()),
+            metrics: Mutex::new(TickerMetrics::default()),
+        }
+    }
+
+    fn record_failure(&self, failure: TickFailure) {
+        self.failure_count.fetch_add(1, Ordering::SeqCst);
+        let mut failures = self.failures.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if failures.len() == KEPT_FAILURES {
+            failures.pop_front();
+        }
+        failures.push_back(failure);
+    }
+
+    fn control(&self) -> TickerControl {
+        *self.control.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut TickerControl)) {
+        change(&mut self.control.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        self.clock.interrupt();
+        self.notify.notify_one();
+    }
+}
+
+
+enum TickerWorker {
+    Thread(thread::JoinHandle<()>),
+    Task(tokio::task::JoinHandle<()>, tokio::runtime::Handle),
+}
+
+/// Handle to a running [`Ticker`], returned by [`Ticker::start`] and [`Ticker::start_async`].
+pub struct TickerHandle {
+    state: Arc<TickerState>,
+    worker: TickerWorker,
+}
+
+impl TickerHandle {
+
+    /// Ticks falling due while paused are not run, the loop just waits for `resume()` or `stop()`.
+    pub fn pause(&self) {
+        self.state.update(|control| control.paused = true);
+    }
+
+    /// A tick which fell due while paused runs right away after resuming.
+    pub fn resume(&self) {
+        self.state.update(|control| control.paused = false);
+    }
+
+    /// Asks the loop to exit, a closure which is already running is allowed to finish.
+    pub fn stop(&self) {
+        self.state.update(|control| control.stopped = true);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.control().paused
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state.control().stopped
+    }
+
+    /// Number of times the closure has been run so far.
+    pub fn ticks(&self) -> u64 {
+        self.state.ticks.load(Ordering::SeqCst)
+    }
+
+    /// The most recent panics caught by a supervised ticker, oldest first.
+    pub fn failures(&self) -> Vec<TickFailure> {
+        self.state.failures.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter().cloned().collect()
+    }
+
+    pub fn failure_count(&self) -> u64 {
+        self.state.failure_count.load(Ordering::SeqCst)
+    }
+
+    /// A snapshot of jitter, run times, overruns and skipped ticks so far, see [`TickerMetrics::to_prometheus`] to export it.
+    pub fn metrics(&self) -> TickerMetrics {
+        let mut metrics = self.state.metrics.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        metrics.ticks = self.ticks();
+        metrics.failures = self.failure_count();
+        metrics
+    }
+
+    /// `true` once the loop has exited, a `join()` won't block anymore.
+    pub fn is_finished(&self) -> bool {
+        match &self.worker {
+            TickerWorker::Thread(handle) => handle.is_finished(),
+            TickerWorker::Task(handle, _) => handle.is_finished(),
+        }
+    }
+
+    /// Aborts a tokio backed ticker at its next `.await`, a thread can't be aborted so it's only asked to `stop()`.
+    pub fn abort(&self) {
+        self.stop();
+        if let TickerWorker::Task(handle, _) = &self.worker {
+            handle.abort();
+        }
+    }
+
+    /// Result of a ticker which [`TickerHandle::is_finished`], works from sync and async code alike.
+    pub(crate) fn join_finished(self) -> thread::Result<()> {
+        match self.worker {
+            TickerWorker::Thread(handle) => handle.join(),
+            TickerWorker::Task(handle, _) => poll_finished(handle).map_err(join_error_into_panic),
+        }
+    }
+
+    /// Blocks until the ticker loop has exited, `Err` carries the panic payload of the closure.
+    /// ### Note: for a tokio backed ticker this uses `block_in_place`, so it needs the multi-threaded
+    /// ### runtime, prefer [`TickerHandle::join_async`] from async code.
+    pub fn join(self) -> thread::Result<()> {
+        match self.worker {
+            TickerWorker::Thread(handle) => handle.join(),
+            TickerWorker::Task(handle, runtime) => {
+                let result = match tokio::runtime::Handle::try_current() {
+                    Ok(_) => tokio::task::block_in_place(|| runtime.block_on(handle)),
+                    Err(_) => runtime.block_on(handle),
+                };
+                result.map_err(join_error_into_panic)
+            }
+        }
+    }
+
+    /// Same as [`TickerHandle::join`] but awaits instead of blocking the current thread.
+    pub async fn join_async(self) -> thread::Result<()> {
+        match self.worker {
+            TickerWorker::Thread(handle) => tokio::task::spawn_blocking(move || handle.join())
+                .await
+                .map_err(join_error_into_panic)?,
+            TickerWorker::Task(handle, _) => handle.await.map_err(join_error_into_panic),
+        }
+    }
+}
+
+fn join_error_into_panic(err: tokio::task::JoinError) -> Box<dyn std::any::Any + Send> {
+    if err.is_panic() {
+        err.into_panic()
+    } else {
+        Box::new("ticker task was cancelled")
+    }
+}
+
+
+fn run_thread_ticker<F>(mut ticker: Ticker<F>, state: Arc<TickerState>) where F: FnMut() + Send + 'static, {
+
+    let clock = Arc::clone(&ticker.clock);
+    let Some(mut next) = ticker.schedule.first_instant(clock.now() + ticker.start_delay, &*clock) else { return };
+
+    loop {
+        loop {
+            let control = state.control();
+            if control.stopped {
+                return;
+            }
+            if !control.paused && clock.now() >= next {
+                break;
+            }
+            let deadline = if control.paused { None } else { Some(next) };
+            clock.sleep_until(deadline, &|| state.control() != control);
+            if control.paused {
+                // don't burst through everything that fell due while paused
+                next = next.max(clock.now());
+            }
+        }
+
+        match run_tick(&mut ticker, &state, next) {
+            Some(deadline) => next = deadline,
+            None => return,
+        }
+    }
+}
+
+
+async fn run_async_ticker<F>(mut ticker: Ticker<F>, state: Arc<TickerState>) where F: FnMut() + Send + 'static, {
+
+    let clock = Arc::clone(&ticker.clock);
+    let Some(mut next) = ticker.schedule.first_instant(clock.now() + ticker.start_delay, &*clock) else { return };
+
+    loop {
+        loop {
+            let control = state.control();
+            if control.stopped {
+                return;
+            }
+            if !control.paused && clock.now() >= next {
+                break;
+            }
+            let deadline = if control.paused { None } else { Some(next) };
+            tokio::select! {
+                _ = state.notify.notified() => {}
+                _ = clock.sleep_until_async(deadline) => {}
+            }
+            if control.paused {
+                next = next.max(clock.now());
+            }
+        }
+
+        match run_tick(&mut ticker, &state, next) {
+            Some(deadline) => next = deadline,
+            None => return,
+        }
+    }
+}
+
+
+/// Runs the closure for the tick `scheduled` for and works out when the next one is due, `None` ends the loop.
+/// With a [`Supervision`] a panic is caught here, recorded and answered with its restart strategy.
+fn run_tick<F>(ticker: &mut Ticker<F>, state: &TickerState, scheduled: Instant) -> Option<Instant> where F: FnMut() + Send + 'static, {
+
+    let clock = &*ticker.clock;
+    let index = state.ticks.fetch_add(1, Ordering::SeqCst);
+    let started = clock.now();
+
+    let Some(supervision) = &ticker.supervision else {
+        (ticker.func)();
+        let next = next_deadline(ticker.mode, ticker.missed_tick_behavior, &ticker.schedule, clock, scheduled, clock.now());
+        record_metrics(ticker, state, scheduled, started, next);
+        return next;
+    };
+
+    // a panic can leave the closure's captured state half updated, supervising means we are fine with that
+    let result = panic::catch_unwind(AssertUnwindSafe(&mut ticker.func));
+    let next = next_deadline(ticker.mode, ticker.missed_tick_behavior, &ticker.schedule, clock, scheduled, clock.now());
+    record_metrics(ticker, state, scheduled, started, next);
+
+    let payload = match result {
+        Ok(()) => {
+            state.consecutive_failures.store(0, Ordering::SeqCst);
+            return next;
+        }
+        Err(payload) => payload,
+    };
+
+    let consecutive = state.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+    state.record_failure(TickFailure { tick: index, at: clock.now(), message: panic_message(&*payload) });
+
+    if supervision.max_failures.is_some_and(|max| consecutive >= max) {
+        match supervision.on_max_failures {
+            GiveUp::Stop => return None,
+            // same as an unsupervised ticker: the thread / task dies and `join()` hands out the payload
+            GiveUp::Escalate => panic::resume_unwind(payload),
+        }
+    }
+
+    Some(next?.max(clock.now() + supervision.backoff_after(consecutive)))
+}
+
+/// Books jitter, run time, overrun and skipped ticks of the tick `scheduled` for, which `started` late by the jitter.
+fn record_metrics<F>(ticker: &Ticker<F>, state: &TickerState, scheduled: Instant, started: Instant, next: Option<Instant>) {
+
+    let clock = &*ticker.clock;
+    let execution = clock.now().saturating_duration_since(started);
+    let period = ticker.schedule.next_instant_after(scheduled, clock).map(|due| due - scheduled);
+    let skipped = next.map_or(0, |next| skipped_ticks(ticker.mode, ticker.missed_tick_behavior, &ticker.schedule, clock, scheduled, next));
+
+    let mut metrics = state.metrics.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    metrics.jitter.record(started.saturating_duration_since(scheduled));
+    metrics.execution.record(execution);
+    if period.is_some_and(|period| execution > period) {
+        metrics.overruns += 1;
+    }
+    metrics.skipped += skipped;
+}
+
+/// Due ticks between the one `scheduled` for and `next` which [`next_deadline`] decided never to run.
+fn skipped_ticks(mode: TickMode, behavior: MissedTickBehavior, schedule: &Schedule, clock: &dyn Clock, scheduled: Instant, next: Instant) -> u64 {
+
+    if mode == TickMode::FixedDelay || behavior == MissedTickBehavior::Burst {
+        return 0;
+    }
+
+    // `Skip` lands on a due tick, `Delay` runs one tick late which stands in for the first one missed
+    let due_before = |end: Instant| match schedule.interval() {
+        Some(interval) => ((end - scheduled).as_nanos().saturating_sub(1) / interval.as_nanos().max(1)) as u64,
+        None => std::iter::successors(schedule.next_instant_after(scheduled, clock), |due| schedule.next_instant_after(*due, clock))
+            .take_while(|due| *due < end)
+            .count() as u64,
+    };
+
+    match behavior {
+        MissedTickBehavior::Skip => due_before(next),
+        _ => due_before(next + Duration::from_nanos(1)).saturating_sub(1),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{clock::ManualClock, schedule::{format_utc, utc}};
+    use super::*;
+
+    /// When every tick of a 100ms ticker started, in ms, over a bit more than a second of a [`ManualClock`]
+    /// moving 50ms at a time. Every third tick takes 250ms, the closure moves the clock on by itself.
+    fn tick_starts(mode: TickMode, behavior: MissedTickBehavior) -> Vec<u128> {
+
+        let clock = ManualClock::new();
+        let started = clock.now();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+
+        let (starts_clone, clock_clone) = (Arc::clone(&starts), clock.clone());
+        let handle = Ticker::new(Duration::from_millis(100), move || {
+            let mut starts = starts_clone.lock().unwrap();
+            starts.push((clock_clone.now() - started).as_millis());
+            if starts.len() % 3 == 0 {
+                clock_clone.advance(Duration::from_millis(250));
+            }
+        })
+        .mode(mode)
+        .missed_tick_behavior(behavior)
+        .clock(clock.clone())
+        .start();
+
+        clock.wait_for_idle(1);
+        while clock.now() - started < Duration::from_secs(1) {
+            clock.advance(Duration::from_millis(50));
+            clock.wait_for_idle(1);
+        }
+        handle.stop();
+        handle.join().unwrap();
+
+        let starts = starts.lock().unwrap().clone();
+        starts
+    }
+
+    #[test]
+    fn fixed_rate_burst_catches_up_on_the_grid() {
+        // the slow tick at 200 ends at 450, 300 and 400 run right then, 500 is on time again
+        assert_eq!(
+            tick_starts(TickMode::FixedRate, MissedTickBehavior::Burst),
+            [0, 100, 200, 450, 450, 500, 750, 750, 800, 1050, 1050],
+        );
+    }
+
+    #[test]
+    fn fixed_rate_delay_restarts_the_grid() {
+        assert_eq!(tick_starts(TickMode::FixedRate, MissedTickBehavior::Delay), [0, 100, 200, 450, 550, 650, 900, 1000]);
+    }
+
+    #[test]
+    fn fixed_rate_skip_drops_missed_ticks() {
+        assert_eq!(tick_starts(TickMode::FixedRate, MissedTickBehavior::Skip), [0, 100, 200, 500, 600, 700, 1000]);
+    }
+
+    #[test]
+    fn fixed_delay_drifts_by_the_slow_ticks() {
+        for behavior in [MissedTickBehavior::Burst, MissedTickBehavior::Delay, MissedTickBehavior::Skip] {
+            assert_eq!(tick_starts(TickMode::FixedDelay, behavior), [0, 100, 200, 550, 650, 750], "{:?}", behavior);
+        }
+    }
+
+    /// Seconds since `started` of every tick, collected by the closure.
+    fn recorder(clock: &ManualClock, started: Instant) -> (Arc<Mutex<Vec<u64>>>, impl FnMut() + Send + 'static) {
+        let stamps = Arc::new(Mutex::new(Vec::new()));
+        let (stamps_clone, clock) = (Arc::clone(&stamps), clock.clone());
+        (stamps, move || stamps_clone.lock().unwrap().push((clock.now() - started).as_secs()))
+    }
+
+    #[test]
+    fn thread_ticker_on_a_manual_clock() {
+
+        let clock = ManualClock::new();
+        let (stamps, record) = recorder(&clock, clock.now());
+        let handle = Ticker::new(Duration::from_secs(1), record).clock(clock.clone()).start();
+
+        // the first tick fires right away
+        clock.wait_for_idle(1);
+        assert_eq!(*stamps.lock().unwrap(), [0]);
+
+        // nothing's due before a whole interval passed
+        clock.advance(Duration::from_millis(999));
+        clock.wait_for_idle(1);
+        assert_eq!(*stamps.lock().unwrap(), [0]);
+
+        // the ticks due at 1..=5 burst through at 5
+        clock.advance(Duration::from_millis(4001));
+        clock.wait_for_idle(1);
+        assert_eq!(*stamps.lock().unwrap(), [0, 5, 5, 5, 5, 5]);
+
+        // nothing while paused, the tick which fell due in between runs once on resume
+        handle.pause();
+        clock.advance(Duration::from_secs(2));
+        clock.wait_for_idle(1);
+        assert_eq!(handle.ticks(), 6);
+        handle.resume();
+        // still asleep as far as the clock can tell until it noticed
+        while handle.ticks() < 7 {
+            thread::yield_now();
+        }
+        clock.wait_for_idle(1);
+        clock.advance(Duration::from_secs(1));
+        clock.wait_for_idle(1);
+        assert_eq!(*stamps.lock().unwrap(), [0, 5, 5, 5, 5, 5, 7, 8]);
+
+        handle.stop();
+        handle.join().unwrap();
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(stamps.lock().unwrap().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn task_ticker_on_a_manual_clock() {
+
+        let clock = ManualClock::new();
+        let (stamps, record) = recorder(&clock, clock.now());
+        let handle = Ticker::new(Duration::from_secs(2), record)
+            .clock(clock.clone())
+            .missed_tick_behavior(MissedTickBehavior::Skip)
+            .start_async();
+
+        clock.idle(1).await;
+        assert_eq!(*stamps.lock().unwrap(), [0]);
+
+        // due at 2 and 4: runs the one at 2 late, skips 4 and is back on the grid at 6
+        clock.advance(Duration::from_secs(5));
+        clock.idle(1).await;
+        assert_eq!(*stamps.lock().unwrap(), [0, 5]);
+        clock.advance(Duration::from_secs(1));
+        clock.idle(1).await;
+        assert_eq!(*stamps.lock().unwrap(), [0, 5, 6]);
+
+        handle.pause();
+        clock.advance(Duration::from_secs(3));
+        clock.idle(1).await;
+        assert_eq!(handle.ticks(), 3);
+        handle.resume();
+        while handle.ticks() < 4 {
+            tokio::task::yield_now().await;
+        }
+        clock.idle(1).await;
+        clock.advance(Duration::from_secs(2));
+        clock.idle(1).await;
+        assert_eq!(*stamps.lock().unwrap(), [0, 5, 6, 9, 11]);
+
+        handle.stop();
+        handle.join_async().await.unwrap();
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(stamps.lock().unwrap().len(), 5);
+    }
+
+    /// A five minute health check and a nightly job on a [`ManualClock`], stepping through a whole day at once.
+    #[test]
+    fn cron_schedules_on_a_manual_clock() {
+
+        let clock = ManualClock::starting_at(utc(2026, 1, 1, 23, 58));
+
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let (fired_clone, clock_clone) = (Arc::clone(&fired), clock.clone());
+        let health_check = Ticker::with_schedule("*/5 * * * *".parse().unwrap(), move || {
+            fired_clone.lock().unwrap().push(format!("health check at {}", format_utc(clock_clone.system_now())));
+        })
+        .clock(clock.clone())
+        .missed_tick_behavior(MissedTickBehavior::Skip)
+        .start();
+
+        let (fired_clone, clock_clone) = (Arc::clone(&fired), clock.clone());
+        let nightly = Ticker::with_schedule("0 3 * * *".parse().unwrap(), move || {
+            fired_clone.lock().unwrap().push(format!("nightly job at {}", format_utc(clock_clone.system_now())));
+        })
+        .clock(clock.clone())
+        .start();
+
+        // nothing fires right away with a cron schedule
+        clock.wait_for_idle(2);
+        assert_eq!((health_check.ticks(), nightly.ticks()), (0, 0));
+
+        // one minute at a time, so every match is hit on time
+        for _ in 0..10 {
+            clock.advance(Duration::from_secs(60));
+            clock.wait_for_idle(2);
+        }
+        assert_eq!((health_check.ticks(), nightly.ticks()), (2, 0));
+
+        // a whole day in one go: the health check runs once for all the ones it missed, the nightly job once
+        clock.advance(Duration::from_secs(24 * 60 * 60));
+        clock.wait_for_idle(2);
+        assert_eq!((health_check.ticks(), nightly.ticks()), (3, 1));
+
+        health_check.stop();
+        nightly.stop();
+        health_check.join().unwrap();
+        nightly.join().unwrap();
+
+        // the two tickers run on threads of their own, which one ran first on the same minute is up to them
+        let mut fired = fired.lock().unwrap().clone();
+        fired.sort();
+        assert_eq!(fired, [
+            "health check at 2026-01-02 00:00:00",
+            "health check at 2026-01-02 00:05:00",
+            "health check at 2026-01-03 00:08:00",
+            "nightly job at 2026-01-03 00:08:00",
+        ]);
+    }
+
+    /// A job failing on some of its ticks, under three different supervisions, on a [`ManualClock`]:
+    /// `(ticks, whether it joined ok, when each failure happened in s)`.
+    fn supervised(supervision: Supervision) -> (u64, bool, Vec<u64>) {
+
+        // fails on ticks 1, 2 and 3, fine on all the others
+        let mut tick = 0;
+        let flaky_job = move || {
+            tick += 1;
+            if (2..=4).contains(&tick) {
+                panic!("job failed on tick {}", tick - 1);
+            }
+        };
+
+        let clock = ManualClock::new();
+        let started = clock.now();
+        let handle = Ticker::new(Duration::from_secs(1), flaky_job)
+            .clock(clock.clone())
+            .supervise(supervision)
+            .start();
+
+        // 20 seconds, one at a time, `wait_for_idle` would block forever once the ticker gave up
+        for _ in 0..20 {
+            while clock.idle_sleepers() == 0 && !handle.is_finished() {
+                thread::yield_now();
+            }
+            clock.advance(Duration::from_secs(1));
+        }
+        handle.stop();
+
+        let failures = handle.failures().iter().enumerate()
+            .map(|(i, failure)| {
+                assert_eq!((failure.tick, &*failure.message), (i as u64 + 1, &*format!("job failed on tick {}", i + 1)));
+                (failure.at - started).as_secs()
+            })
+            .collect();
+        let ticks = handle.ticks();
+        (ticks, handle.join().is_ok(), failures)
+    }
+
+    #[test]
+    fn supervision_restarts_with_backoff() {
+        // restarted 2s after the first failure, 4s after the second, the rest of the 20s run fine
+        let supervision = Supervision::restart().backoff(Duration::from_secs(2), Duration::from_secs(5));
+        assert_eq!(supervised(supervision), (13, true, vec![1, 3, 7]));
+    }
+
+    #[test]
+    fn supervision_gives_up() {
+        assert_eq!(supervised(Supervision::restart().max_failures(2, GiveUp::Stop)), (3, true, vec![1, 2]));
+        assert_eq!(supervised(Supervision::restart().max_failures(3, GiveUp::Escalate)), (4, false, vec![1, 2, 3]));
+    }
+
+    /// One slow tick under each [`MissedTickBehavior`], stepped through on a [`ManualClock`] so the numbers come out exact.
+    /// Tick 3 "works" for 2.5s of a 1s period: that's one overrun in every setup, and the due ticks at 4s and 5s
+    /// are run late (`Burst`), folded into one late tick (`Delay`) or dropped (`Skip`).
+    #[test]
+    fn metrics_of_a_slow_tick() {
+
+        let setups = [
+            (MissedTickBehavior::Burst, 13, 0),
+            (MissedTickBehavior::Delay, 12, 1),
+            (MissedTickBehavior::Skip, 11, 2),
+        ];
+
+        for (behavior, ticks, skipped) in setups {
+
+            let clock = ManualClock::new();
+            let clock_clone = clock.clone();
+            let mut tick = 0;
+            let handle = Ticker::new(Duration::from_secs(1), move || {
+                if tick == 3 {
+                    clock_clone.advance(Duration::from_millis(2500));
+                }
+                tick += 1;
+            })
+            .clock(clock.clone())
+            .missed_tick_behavior(behavior)
+            .start();
+
+            for _ in 0..10 {
+                clock.wait_for_idle(1);
+                clock.advance(Duration::from_secs(1));
+            }
+            clock.wait_for_idle(1);
+            handle.stop();
+
+            let metrics = handle.metrics();
+            handle.join().unwrap();
+
+            assert_eq!((metrics.ticks, metrics.overruns, metrics.skipped), (ticks, 1, skipped), "{:?}", behavior);
+            assert_eq!(metrics.execution.max(), Duration::from_millis(2500), "{:?}", behavior);
+            assert!(metrics.to_prometheus("demo_ticker").contains(&format!("demo_ticker_skipped_ticks_total {}", skipped)));
+        }
+    }
+
+    #[test]
+    fn skip_after_a_stall_of_more_ticks_than_a_u32_counts() {
+        let clock = ManualClock::new();
+        let (scheduled, interval) = (clock.now(), Duration::from_nanos(1));
+        let stalled = scheduled + Duration::from_secs(5);
+        let next = next_deadline(TickMode::FixedRate, MissedTickBehavior::Skip, &Schedule::every(interval), &clock, scheduled, stalled);
+        assert_eq!(next, Some(stalled + interval));
+    }
+}
@@ -0,0 +1,563 @@
+use std::{panic::{self, AssertUnwindSafe}, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex, MutexGuard}, thread, time::{Duration, Instant}};
+use tokio::sync::Notify;
+use crate::clock::{Clock, RealClock};
+
+// 64 slots per level, so a slot's timers fit one `u64` occupancy bit and 6 levels cover 64^6 ticks (~2 years at 1ms)
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 6;
+const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// Returned when scheduling a timer, to [`TimerWheelHandle::cancel`] it later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId {
+    index: usize,
+    generation: u64,
+}
+
+type Callback = Box<dyn FnMut() + Send>;
+
+struct Entry {
+    generation: u64,
+    when: u64,
+    period: Option<u64>,
+    // `None` while the driver is running it
+    callback: Option<Callback>,
+    // (level, slot, index in slot), `None` while the driver is running it
+    position: Option<(usize, usize, usize)>,
+}
+
+struct Level {
+    slots: Vec<Vec<usize>>,
+    occupied: u64,
+}
+
+/// ## The bookkeeping part of the wheel, in ticks of the wheel's resolution, no clock and no threads.
+/// A timer due at `when` goes into the level of the highest 6 bit group in which `when` differs from `elapsed`
+/// (the last tick processed), at the slot those 6 bits of `when` point to. Level 0 holds what's due within
+/// the current 64 ticks, level 1 the next 64 * 64 ticks, and so on.
+/// - insert and cancel are O(1): push to / swap-remove from a slot, entries remember where they are,
+/// - the next expiration is found from the occupancy bits, at most one `u64` per level,
+/// - reaching a slot on a higher level cascades its timers down into the lower levels (or fires them).
+struct Wheel {
+    elapsed: u64,
+    levels: Vec<Level>,
+    // a slab, freed indexes are reused with a bumped generation so stale `TimerId`s can't cancel someone else
+    entries: Vec<(u64, Option<Entry>)>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl Wheel {
+
+    fn new() -> Self {
+        Wheel {
+            elapsed: 0,
+            levels: (0..LEVELS).map(|_| Level { slots: vec![Vec::new(); SLOTS], occupied: 0 }).collect(),
+            entries: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    fn insert(&mut self, when: u64, period: Option<u64>, callback: Callback) -> TimerId {
+
+        let index = self.free.pop().unwrap_or_else(|| {
+            self.entries.push((0, None));
+            self.entries.len() - 1
+        });
+        let generation = self.entries[index].0 + 1;
+        self.entries[index] = (generation, Some(Entry { generation, when, period, callback: Some(callback), position: None }));
+        self.len += 1;
+
+        self.place(index);
+        TimerId { index, generation }
+    }
+
+    /// Puts the entry at `index` into the slot matching its `when`, relative to `elapsed`.
+    fn place(&mut self, index: usize) {
+
+        let elapsed = self.elapsed;
+        let Some(entry) = self.entries[index].1.as_mut() else { return };
+
+        // already due ones fire on the next poll, too distant ones wait in the top level and get re-placed from there,
+        // `entry.when` stays as it is so they still fire when they're due
+        let when = entry.when.clamp(elapsed.saturating_add(1), elapsed.saturating_add(MAX_TICKS));
+
+        let level = level_for(elapsed, when);
+        let slot = slot_for(when, level);
+        let slots = &mut self.levels[level];
+        entry.position = Some((level, slot, slots.slots[slot].len()));
+        slots.slots[slot].push(index);
+        slots.occupied |= 1 << slot;
+    }
+
+    fn unplace(&mut self, index: usize) {
+
+        let Some(entry) = self.entries[index].1.as_mut() else { return };
+        let Some((level, slot, position)) = entry.position.take() else { return };
+
+        let slots = &mut self.levels[level];
+        slots.slots[slot].swap_remove(position);
+        if let Some(&moved) = slots.slots[slot].get(position) {
+            if let Some(moved) = self.entries[moved].1.as_mut() {
+                moved.position = Some((level, slot, position));
+            }
+        }
+        if slots.slots[slot].is_empty() {
+            slots.occupied &= !(1 << slot);
+        }
+    }
+
+    fn entry(&mut self, id: TimerId) -> Option<&mut Entry> {
+        self.entries.get_mut(id.index)?.1.as_mut().filter(|entry| entry.generation == id.generation)
+    }
+
+    fn remove(&mut self, id: TimerId) -> Option<Entry> {
+        self.entry(id)?;
+        self.unplace(id.index);
+        self.free.push(id.index);
+        self.len -= 1;
+        self.entries[id.index].1.take()
+    }
+
+    /// `(level, slot, tick)` of the next slot to process.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+
+        for (level, slots) in self.levels.iter().enumerate() {
+
+            if slots.occupied == 0 {
+                continue;
+            }
+
+            let slot_range = 1u64 << (SLOT_BITS * level as u32);
+            let level_range = slot_range << SLOT_BITS;
+            let now_slot = (self.elapsed / slot_range) as usize % SLOTS;
+            let slot = (slots.occupied.rotate_right(now_slot as u32).trailing_zeros() as usize + now_slot) % SLOTS;
+
+            let mut tick = self.elapsed - self.elapsed % level_range + slot as u64 * slot_range;
+            if tick <= self.elapsed {
+                // only happens on the top level, whose slots wrap around like a ring buffer
+                tick += level_range;
+            }
+            return Some((level, slot, tick.max(self.elapsed + 1)));
+        }
+
+        None
+    }
+
+    /// Advances to tick `now` and takes out everything that's due, in order.
+    fn poll(&mut self, now: u64) -> Vec<(TimerId, Callback)> {
+
+        let mut fired = Vec::new();
+
+        while let Some((level, slot, tick)) = self.next_expiration() {
+
+            if tick > now {
+                break;
+            }
+            self.elapsed = tick;
+
+            let indexes = std::mem::take(&mut self.levels[level].slots[slot]);
+            self.levels[level].occupied &= !(1 << slot);
+
+            for index in indexes {
+                let Some(entry) = self.entries[index].1.as_mut() else { continue };
+                entry.position = None;
+                if entry.when > tick {
+                    // cascade down
+                    self.place(index);
+                    continue;
+                }
+                if let Some(callback) = entry.callback.take() {
+                    fired.push((TimerId { index, generation: entry.generation }, callback));
+                }
+            }
+        }
+
+        self.elapsed = self.elapsed.max(now);
+        fired
+    }
+
+    /// Hands the callback of a periodic timer back after it ran, one-shot timers are removed.
+    fn finish(&mut self, id: TimerId, callback: Callback) {
+
+        let elapsed = self.elapsed;
+        let Some(entry) = self.entry(id) else { return }; // cancelled while running
+        let Some(period) = entry.period else {
+            self.remove(id);
+            return;
+        };
+
+        // next one on the original grid, ticks missed while the driver was busy are skipped
+        let behind = elapsed.saturating_sub(entry.when);
+        entry.when = entry.when.saturating_add((behind / period + 1).saturating_mul(period));
+        entry.callback = Some(callback);
+        self.place(id.index);
+    }
+}
+
+fn level_for(elapsed: u64, when: u64) -> usize {
+    // `| (SLOTS - 1)` keeps everything within the current 64 ticks on level 0
+    let significant = 63 - ((elapsed ^ when) | (SLOTS as u64 - 1)).leading_zeros();
+    // less than `MAX_TICKS` ahead but past the next multiple of 64^6: the top level is a ring, its slot comes round again
+    ((significant / SLOT_BITS) as usize).min(LEVELS - 1)
+}
+
+fn slot_for(when: u64, level: usize) -> usize {
+    (when >> (SLOT_BITS * level as u32)) as usize % SLOTS
+}
+
+
+/// ## Thousands of timers, one thread.
+/// Every [`Ticker`](crate::ticker::Ticker) sleeps on its own thread or task, fine for a few tickers but not for one timeout per connection
+/// or entity. A timer wheel keeps all timers in one hierarchical hashed wheel (see `Wheel`) with O(1) insert and cancel,
+/// and a single driver thread (or task) sleeps until the earliest one and runs whatever is due.
+/// ```ignore
+/// let timers = TimerWheel::new(Duration::from_millis(1)).start();
+/// let id = timers.schedule_once(Duration::from_secs(30), move || session.expire());
+/// timers.cancel(id); // the session saw some traffic after all
+/// ```
+/// Timers fire at most one resolution late, never early. The callbacks run on the driver, so they should be quick
+/// (hand real work off to a channel or thread pool), a callback that panics is dropped and the others carry on.
+pub struct TimerWheel {
+    resolution: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+/// Shared between all [`TimerWheelHandle`] clones and the driver.
+struct WheelShared {
+    wheel: Mutex<Wheel>,
+    clock: Arc<dyn Clock>,
+    start: Instant,
+    resolution: Duration,
+    // bumped when the driver should re-check: an earlier timer came in, or `stop()`
+    wakeups: AtomicU64,
+    notify: Notify,
+    stopped: Mutex<bool>,
+    panics: AtomicU64,
+}
+
+impl TimerWheel {
+
+    /// Deadlines are rounded up to multiples of `resolution` (at least 1µs), the coarser the fewer wake ups.
+    pub fn new(resolution: Duration) -> Self {
+        TimerWheel { resolution: resolution.max(Duration::from_micros(1)), clock: Arc::new(RealClock::default()) }
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn shared(self) -> Arc<WheelShared> {
+        Arc::new(WheelShared {
+            wheel: Mutex::new(Wheel::new()),
+            start: self.clock.now(),
+            clock: self.clock,
+            resolution: self.resolution,
+            wakeups: AtomicU64::new(0),
+            notify: Notify::new(),
+            stopped: Mutex::new(false),
+            panics: AtomicU64::new(0),
+        })
+    }
+
+    /// Drives the wheel from a dedicated std thread.
+    pub fn start(self) -> TimerWheelHandle {
+        let shared = self.shared();
+        let shared_clone = Arc::clone(&shared);
+        thread::spawn(move || run_thread_driver(shared_clone));
+        TimerWheelHandle { shared }
+    }
+
+    /// Drives the wheel from a tokio task, must be called from inside a tokio runtime.
+    pub fn start_async(self) -> TimerWheelHandle {
+        let shared = self.shared();
+        let shared_clone = Arc::clone(&shared);
+        tokio::spawn(run_async_driver(shared_clone));
+        TimerWheelHandle { shared }
+    }
+}
+
+impl WheelShared {
+
+    fn lock(&self) -> MutexGuard<'_, Wheel> {
+        self.wheel.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn is_stopped(&self) -> bool {
+        *self.stopped.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The tick `instant` falls into.
+    fn tick_of(&self, instant: Instant) -> u64 {
+        (instant.saturating_duration_since(self.start).as_nanos() / self.resolution.as_nanos()).min(u64::MAX as u128) as u64
+    }
+
+    /// The tick of a deadline `delay` after `instant`, rounded up so nothing fires early.
+    /// In nanoseconds, so a delay which no `Instant` can hold (say `Duration::MAX`) just ends up far away.
+    fn deadline_tick(&self, instant: Instant, delay: Duration) -> u64 {
+        let since = instant.saturating_duration_since(self.start).as_nanos() + delay.as_nanos();
+        since.div_ceil(self.resolution.as_nanos()).min(u64::MAX as u128) as u64
+    }
+
+    fn instant_of(&self, tick: u64) -> Instant {
+        self.start + Duration::from_nanos((tick as u128 * self.resolution.as_nanos()).min(u64::MAX as u128) as u64)
+    }
+
+    fn wake(&self) {
+        self.wakeups.fetch_add(1, Ordering::SeqCst);
+        self.clock.interrupt();
+        self.notify.notify_one();
+    }
+
+    /// Takes out what's due and runs it, returns the instant of the next expiration.
+    fn run_due(&self) -> Option<Instant> {
+
+        let fired = self.lock().poll(self.tick_of(self.clock.now()));
+
+        for (id, mut callback) in fired {
+            if panic::catch_unwind(AssertUnwindSafe(&mut callback)).is_err() {
+                self.panics.fetch_add(1, Ordering::SeqCst);
+                self.lock().remove(id);
+                continue;
+            }
+            self.lock().finish(id, callback);
+        }
+
+        self.lock().next_expiration().map(|(_, _, tick)| self.instant_of(tick))
+    }
+}
+
+fn run_thread_driver(shared: Arc<WheelShared>) {
+    while !shared.is_stopped() {
+        // read before polling, so a timer added in between can't be slept through
+        let wakeups = shared.wakeups.load(Ordering::SeqCst);
+        let next = shared.run_due();
+        shared.clock.sleep_until(next, &|| shared.wakeups.load(Ordering::SeqCst) != wakeups);
+    }
+}
+
+async fn run_async_driver(shared: Arc<WheelShared>) {
+    while !shared.is_stopped() {
+        let next = shared.run_due();
+        // `notify_one` keeps a permit, so a wake up between `run_due` and here isn't lost
+        tokio::select! {
+            _ = shared.notify.notified() => {}
+            _ = shared.clock.sleep_until_async(next) => {}
+        }
+    }
+}
+
+
+/// Handle to a running [`TimerWheel`], cheap to clone and share.
+#[derive(Clone)]
+pub struct TimerWheelHandle {
+    shared: Arc<WheelShared>,
+}
+
+impl TimerWheelHandle {
+
+    fn schedule(&self, at: Instant, delay: Duration, period: Option<Duration>, callback: Callback) -> TimerId {
+        let shared = &self.shared;
+        let when = shared.deadline_tick(at, delay);
+        let period = period.map(|period| shared.deadline_tick(shared.start, period).max(1));
+
+        let mut wheel = shared.lock();
+        let earliest = wheel.next_expiration().map(|(_, _, tick)| tick);
+        let id = wheel.insert(when, period, callback);
+        drop(wheel);
+
+        // the driver only needs to hear about timers due before what it's sleeping for
+        if earliest.is_none_or(|earliest| when < earliest) {
+            shared.wake();
+        }
+        id
+    }
+
+    /// Runs `callback` once, `delay` from now.
+    pub fn schedule_once(&self, delay: Duration, callback: impl FnOnce() + Send + 'static) -> TimerId {
+        self.schedule(self.shared.clock.now(), delay, None, once(callback))
+    }
+
+    pub fn schedule_at(&self, at: Instant, callback: impl FnOnce() + Send + 'static) -> TimerId {
+        self.schedule(at, Duration::ZERO, None, once(callback))
+    }
+
+    /// Runs `callback` every `period`, the first time one period from now. Runs the driver was too busy for are skipped.
+    pub fn schedule_every(&self, period: Duration, callback: impl FnMut() + Send + 'static) -> TimerId {
+        self.schedule(self.shared.clock.now(), period, Some(period), Box::new(callback))
+    }
+
+    /// `true` if the timer was still pending (or periodic), a callback which is already running finishes first.
+    pub fn cancel(&self, id: TimerId) -> bool {
+        self.shared.lock().remove(id).is_some()
+    }
+
+    /// Number of scheduled timers.
+    pub fn len(&self) -> usize {
+        self.shared.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Callbacks which panicked and got dropped.
+    pub fn panics(&self) -> u64 {
+        self.shared.panics.load(Ordering::SeqCst)
+    }
+
+    /// Stops the driver, pending timers never fire.
+    pub fn stop(&self) {
+        *self.shared.stopped.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = true;
+        self.shared.wake();
+    }
+}
+
+fn once(callback: impl FnOnce() + Send + 'static) -> Callback {
+    let mut callback = Some(callback);
+    Box::new(move || {
+        if let Some(callback) = callback.take() {
+            callback();
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use crate::{clock::ManualClock, metrics::Histogram, ticker::Ticker};
+    use super::*;
+
+    fn nothing() -> Callback {
+        Box::new(|| {})
+    }
+
+    #[test]
+    fn timers_beyond_the_top_level_wait_for_their_tick() {
+        let mut wheel = Wheel::new();
+        let near = wheel.insert(3, None, nothing());
+        assert_eq!(wheel.poll(3).into_iter().map(|(id, _)| id).collect::<Vec<_>>(), [near]);
+
+        // `elapsed` isn't 0 anymore, so `elapsed + MAX_TICKS` crosses a multiple of 64^6
+        let never = wheel.insert(u64::MAX, None, nothing());
+        let far = wheel.insert(3 << 40, None, nothing());
+        assert!(wheel.poll(MAX_TICKS + 10).is_empty());
+        assert!(wheel.poll((3 << 40) - 1).is_empty());
+        assert_eq!(wheel.poll(3 << 40).into_iter().map(|(id, _)| id).collect::<Vec<_>>(), [far]);
+        // a few more laps of the top level, still waiting
+        assert!(wheel.poll(1 << 44).is_empty());
+        assert!(wheel.entry(never).is_some());
+    }
+
+    #[test]
+    fn timers_fire_in_order_across_levels() {
+        let mut wheel = Wheel::new();
+        let ticks = [1, 63, 64, 65, 4095, 4096, 100_000, 1 << 30, (1 << 36) + 7];
+        let ids = ticks.iter().map(|&tick| (wheel.insert(tick, None, nothing()), tick)).collect::<Vec<_>>();
+        let cancelled = wheel.insert(70, None, nothing());
+        assert!(wheel.remove(cancelled).is_some());
+        for (id, tick) in ids {
+            assert!(wheel.poll(tick - 1).is_empty(), "nothing due before {}", tick);
+            assert_eq!(wheel.poll(tick).into_iter().map(|(id, _)| id).collect::<Vec<_>>(), [id]);
+            wheel.remove(id);
+        }
+        assert_eq!(wheel.len, 0);
+    }
+
+    #[test]
+    fn schedule_duration_max() {
+        let clock = ManualClock::new();
+        let timers = TimerWheel::new(Duration::from_micros(1)).clock(clock.clone()).start();
+        let fired = Arc::new(AtomicU64::new(0));
+
+        let counter = Arc::clone(&fired);
+        timers.schedule_once(Duration::from_micros(3), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        clock.wait_for_idle(1);
+        clock.advance(Duration::from_micros(3));
+        clock.wait_for_idle(1);
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+
+        let never = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&never);
+        timers.schedule_once(Duration::MAX, move || flag.store(true, Ordering::SeqCst));
+        let counter = Arc::clone(&fired);
+        timers.schedule_once(Duration::from_secs(100_000), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        clock.wait_for_idle(1);
+        clock.advance(Duration::from_secs(100_000));
+        clock.wait_for_idle(1);
+
+        // the driver survived and fired the one which was due
+        assert_eq!(fired.load(Ordering::SeqCst), 2);
+        assert!(!never.load(Ordering::SeqCst));
+        assert_eq!(timers.len(), 1);
+        timers.stop();
+    }
+
+    /// `timers` periodic timers at `period` for `run_for`, once as one `Ticker` thread each and once on a single wheel.
+    /// Each callback records how late it ran compared to its fixed-rate schedule.
+    #[test]
+    #[ignore = "benchmark, takes 4s and 1000 threads: cargo test timer_wheel_bench -- --ignored --nocapture"]
+    fn timer_wheel_bench() {
+
+        let (timers, period, run_for) = (1000, Duration::from_millis(50), Duration::from_secs(2));
+
+        // the first run sets the baseline, every later one is due a period after the previous due time
+        let job = |lateness: Arc<Mutex<Histogram>>, fired: Arc<AtomicU64>| {
+            let mut due: Option<Instant> = None;
+            move || {
+                let now = Instant::now();
+                if let Some(due) = due {
+                    lateness.lock().unwrap().record(now.saturating_duration_since(due));
+                }
+                due = Some(due.map_or(now, |due| due) + period);
+                fired.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+
+        let report = |name: &str, setup: Duration, teardown: Duration, lateness: &Histogram, fired: u64| {
+            println!(
+                "{:>10}: setup {:>9.2?}, teardown {:>9.2?}, {} runs, late p50 {:?} p99 {:?} max {:?}",
+                name, setup, teardown, fired, lateness.quantile(0.5), lateness.quantile(0.99), lateness.max(),
+            );
+        };
+
+        // one thread per timer
+        let lateness = Arc::new(Mutex::new(Histogram::default()));
+        let fired = Arc::new(AtomicU64::new(0));
+        let started = Instant::now();
+        let handles = (0..timers)
+            .map(|_| Ticker::new(period, job(Arc::clone(&lateness), Arc::clone(&fired))).start())
+            .collect::<Vec<_>>();
+        let setup = started.elapsed();
+        thread::sleep(run_for);
+        let started = Instant::now();
+        handles.iter().for_each(|handle| handle.stop());
+        handles.into_iter().for_each(|handle| handle.join().unwrap());
+        report("tickers", setup, started.elapsed(), &lateness.lock().unwrap(), fired.load(Ordering::SeqCst));
+
+        // one wheel
+        let lateness = Arc::new(Mutex::new(Histogram::default()));
+        let fired = Arc::new(AtomicU64::new(0));
+        let started = Instant::now();
+        let wheel = TimerWheel::new(Duration::from_millis(1)).start();
+        let ids = (0..timers)
+            .map(|_| wheel.schedule_every(period, job(Arc::clone(&lateness), Arc::clone(&fired))))
+            .collect::<Vec<_>>();
+        let setup = started.elapsed();
+        thread::sleep(run_for);
+        let started = Instant::now();
+        ids.into_iter().for_each(|id| {
+            wheel.cancel(id);
+        });
+        wheel.stop();
+        report("wheel", setup, started.elapsed(), &lateness.lock().unwrap(), fired.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,253 @@
+use std::{sync::{Arc, Mutex, MutexGuard}, thread, time::{Duration, Instant}};
+use crate::{clock::{Clock, RealClock}, shutdown::CancellationToken, ticker::{Ticker, TickerHandle}};
+
+/// Handed to the `on_missed` callback of a [`Watchdog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissedHeartbeat {
+    pub name: String,
+    pub timeout: Duration,
+    /// How long ago the component last checked in, when the watchdog noticed.
+    pub silent_for: Duration,
+}
+
+type OnMissed = Box<dyn FnMut(&MissedHeartbeat) + Send>;
+
+/// ## Notices components which stopped checking in.
+/// Every component [`WatchdogHandle::register`]s with a timeout and calls [`Heartbeat::pet`] from its loop.
+/// A [`Ticker`] checks all of them every `check_every`, and one which has been silent for longer than its timeout
+/// (stuck in an endless loop, a deadlock, a blocking call without timeout, ...) is reported once:
+/// - to the `on_missed` callback,
+/// - and, with [`Watchdog::cancel_on_miss`], by cancelling a token, e.g. the one of a [`Shutdown`]
+///   (the `Notify` of `async_ticker_with_notification_mechanism` grown up).
+///
+/// Petting again after a miss counts as recovered, the next silence is reported again.
+/// Dropping a [`Heartbeat`] unregisters it, a component which is done isn't wedged.
+pub struct Watchdog {
+    check_every: Duration,
+    clock: Arc<dyn Clock>,
+    on_missed: Option<OnMissed>,
+    cancel_on_miss: Option<CancellationToken>,
+}
+
+struct WatchdogShared {
+    clock: Arc<dyn Clock>,
+    components: Mutex<Components>,
+}
+
+#[derive(Default)]
+struct Components {
+    next_id: u64,
+    registered: Vec<Component>,
+}
+
+struct Component {
+    id: u64,
+    name: String,
+    timeout: Duration,
+    last_pet: Instant,
+    missed: bool,
+}
+
+impl WatchdogShared {
+    fn lock(&self) -> MutexGuard<'_, Components> {
+        self.components.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Marks and returns the components which went silent since the last check.
+    fn check(&self) -> Vec<MissedHeartbeat> {
+        let now = self.clock.now();
+        let mut components = self.lock();
+        components.registered
+            .iter_mut()
+            .filter(|component| !component.missed && now.saturating_duration_since(component.last_pet) > component.timeout)
+            .map(|component| {
+                component.missed = true;
+                MissedHeartbeat {
+                    name: component.name.clone(),
+                    timeout: component.timeout,
+                    silent_for: now - component.last_pet,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Watchdog {
+
+    /// A miss is noticed at most `check_every` after the timeout ran out.
+    pub fn new(check_every: Duration) -> Self {
+        Watchdog { check_every, clock: Arc::new(RealClock::default()), on_missed: None, cancel_on_miss: None }
+    }
+
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Runs on the watchdog's ticker, once per missed heartbeat.
+    pub fn on_missed(mut self, callback: impl FnMut(&MissedHeartbeat) + Send + 'static) -> Self {
+        self.on_missed = Some(Box::new(callback));
+        self
+    }
+
+    /// Cancels `token` on the first missed heartbeat.
+    pub fn cancel_on_miss(mut self, token: CancellationToken) -> Self {
+        self.cancel_on_miss = Some(token);
+        self
+    }
+
+    fn ticker(self) -> (Arc<WatchdogShared>, Ticker<impl FnMut() + Send + 'static>) {
+
+        let shared = Arc::new(WatchdogShared { clock: Arc::clone(&self.clock), components: Mutex::default() });
+        let shared_clone = Arc::clone(&shared);
+        let (mut on_missed, cancel_on_miss) = (self.on_missed, self.cancel_on_miss);
+
+        let ticker = Ticker::new(self.check_every, move || {
+            let missed = shared_clone.check();
+            for heartbeat in &missed {
+                if let Some(on_missed) = &mut on_missed {
+                    on_missed(heartbeat);
+                }
+            }
+            if let (Some(token), false) = (&cancel_on_miss, missed.is_empty()) {
+                token.cancel();
+            }
+        });
+
+        (shared, ticker.clock(self.clock))
+    }
+
+    /// Runs the checks on a dedicated std thread.
+    pub fn start(self) -> WatchdogHandle {
+        let (shared, ticker) = self.ticker();
+        WatchdogHandle { shared, ticker: ticker.start() }
+    }
+
+    /// Runs the checks as a tokio task, must be called from inside a tokio runtime.
+    pub fn start_async(self) -> WatchdogHandle {
+        let (shared, ticker) = self.ticker();
+        WatchdogHandle { shared, ticker: ticker.start_async() }
+    }
+}
+
+/// Handle to a running [`Watchdog`].
+pub struct WatchdogHandle {
+    shared: Arc<WatchdogShared>,
+    ticker: TickerHandle,
+}
+
+impl WatchdogHandle {
+
+    /// Starts watching a component, it counts as having checked in just now.
+    pub fn register(&self, name: impl Into<String>, timeout: Duration) -> Heartbeat {
+        let mut components = self.shared.lock();
+        let id = components.next_id;
+        components.next_id += 1;
+        components.registered.push(Component {
+            id,
+            name: name.into(),
+            timeout,
+            last_pet: self.shared.clock.now(),
+            missed: false,
+        });
+        Heartbeat { shared: Arc::clone(&self.shared), id }
+    }
+
+    /// Names of the components currently overdue.
+    pub fn missed(&self) -> Vec<String> {
+        self.shared.lock().registered.iter().filter(|component| component.missed).map(|component| component.name.clone()).collect()
+    }
+
+    pub fn stop(&self) {
+        self.ticker.stop();
+    }
+
+    /// Blocks until the watchdog's ticker exited, `Err` if the `on_missed` callback panicked.
+    pub fn join(self) -> thread::Result<()> {
+        self.ticker.join()
+    }
+}
+
+
+/// A component's end of the [`Watchdog`], dropping it unregisters.
+pub struct Heartbeat {
+    shared: Arc<WatchdogShared>,
+    id: u64,
+}
+
+impl Heartbeat {
+
+    /// "Still alive", call it more often than the timeout.
+    pub fn pet(&self) {
+        let now = self.shared.clock.now();
+        if let Some(component) = self.shared.lock().registered.iter_mut().find(|component| component.id == self.id) {
+            component.last_pet = now;
+            component.missed = false;
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.shared.lock().registered.retain(|component| component.id != self.id);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use crate::{clock::ManualClock, shutdown::Shutdown};
+    use super::*;
+
+    /// Two workers under a watchdog, one of them wedges itself the way `ticker_main` loops forever.
+    /// The watchdog notices and shuts everything down, the wedged thread can't be stopped and shows up as aborted.
+    #[test]
+    fn a_wedged_worker_shuts_everything_down() {
+        let clock = ManualClock::new();
+        let mut shutdown = Shutdown::new();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = Arc::clone(&reported);
+        let watchdog = Watchdog::new(Duration::from_millis(50))
+            .clock(clock.clone())
+            .on_missed(move |missed| reported_clone.lock().unwrap().push((missed.name.clone(), missed.silent_for)))
+            .cancel_on_miss(shutdown.token())
+            .start();
+        clock.wait_for_idle(1);
+
+        let (healthy, token, (exited, exits)) = (watchdog.register("healthy", Duration::from_millis(200)), shutdown.token(), mpsc::channel());
+        shutdown.register_thread("healthy", thread::spawn(move || {
+            token.wait();
+            exited.send(()).unwrap();
+        }));
+        // stuck, never pets (nor checks the token) once it stopped, until the test lets it go
+        let (wedged, (release, released)) = (watchdog.register("wedged", Duration::from_millis(200)), mpsc::channel::<()>());
+        shutdown.register_thread("wedged", thread::spawn(move || {
+            let _ = released.recv();
+        }));
+
+        // both pet every 50ms, the wedged one stops after 100ms, it's 250ms silent by the check which notices
+        let mut elapsed = Duration::ZERO;
+        while !shutdown.token().is_cancelled() {
+            healthy.pet();
+            if elapsed <= Duration::from_millis(100) {
+                wedged.pet();
+            }
+            clock.advance(Duration::from_millis(50));
+            clock.wait_for_idle(1);
+            elapsed += Duration::from_millis(50);
+        }
+        assert_eq!(elapsed, Duration::from_millis(350));
+        assert_eq!(watchdog.missed(), ["wedged"]);
+        assert_eq!(*reported.lock().unwrap(), [("wedged".to_string(), Duration::from_millis(250))]);
+
+        watchdog.stop();
+        watchdog.join().unwrap();
+        exits.recv().unwrap();
+        let report = shutdown.shutdown_blocking(Duration::from_millis(100));
+        assert_eq!(report.stopped(), ["healthy"]);
+        assert_eq!(report.aborted(), ["wedged"]);
+        drop(release);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{any::Any, collections::VecDeque, num::NonZeroUsize, panic::{self, AssertUnwindSafe}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Condvar, Mutex, MutexGuard}, thread};
+use crate::executor::{Executor, Splittable};
+
+/// A task or a piece of one, `offset` is where the piece starts within its task so the results can be joined in order.
+struct Piece<T> {
+    task: usize,
+    offset: usize,
+    item: T,
+}
+
+/// ## Scoped threads with a deque each, idle ones steal from the others.
+/// The tasks are dealt out in contiguous shares like [`StaticSplitExecutor`](crate::executor::StaticSplitExecutor),
+/// but a thread which ran out of work steals from the back of another thread's deque instead of waiting.
+/// With [`Executor::execute_splittable`] a task bigger than the `grain` is cut in halves before it runs,
+/// recursively: the thread keeps working on the first half and pushes the second to the front of its own deque,
+/// where it's the next thing it works on unless somebody steals it first. Thieves take from the back,
+/// which holds the biggest pieces, so a single huge map segment ends up spread over all threads.
+/// A thread which finds nothing to steal sleeps until somebody pushes a piece, or the last one is done.
+///
+/// A task which panics stops the other threads at their next piece, `execute` panics once they're done.
+#[derive(Debug, Clone, Copy)]
+pub struct WorkStealingExecutor {
+    threads: usize,
+    grain: usize,
+}
+
+impl Default for WorkStealingExecutor {
+    /// One thread per core.
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+/// What the threads of one `execute` share.
+struct Deques<T> {
+    deques: Vec<Mutex<VecDeque<Piece<T>>>>,
+    /// Pieces not run yet, including the ones being split right now, the threads quit when it reaches 0.
+    pending: AtomicUsize,
+    panicked: AtomicBool,
+    /// Taken to check for work before going to sleep, and before waking the sleepers up once there's more,
+    /// so a piece pushed in between can't go unnoticed.
+    idle: Mutex<()>,
+    wake: Condvar,
+}
+
+impl<T> Deques<T> {
+
+    fn lock(&self, worker: usize) -> MutexGuard<'_, VecDeque<Piece<T>>> {
+        self.deques[worker].lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The front of the own deque, or else the back of somebody else's.
+    fn next(&self, worker: usize) -> Option<Piece<T>> {
+        if let Some(piece) = self.lock(worker).pop_front() {
+            return Some(piece);
+        }
+        let count = self.deques.len();
+        (1..count).find_map(|i| self.lock((worker + i) % count).pop_back())
+    }
+
+    /// Sleeps until there may be something to steal, or nothing is left to wait for.
+    fn wait_for_work(&self) {
+        let idle = self.idle.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let done = self.pending.load(Ordering::SeqCst) == 0 || self.panicked.load(Ordering::SeqCst);
+        if !done && (0..self.deques.len()).all(|worker| self.lock(worker).is_empty()) {
+            drop(self.wake.wait(idle).unwrap_or_else(|poisoned| poisoned.into_inner()));
+        }
+    }
+
+    /// After a piece was pushed (`one` thief is enough), or after the last piece or a panic (`all` of them).
+    fn wake(&self, all: bool) {
+        drop(self.idle.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        match all {
+            true => self.wake.notify_all(),
+            false => self.wake.notify_one(),
+        }
+    }
+}
+
+impl WorkStealingExecutor {
+
+    /// Splits down to pieces of a single unit of [`Splittable::size`].
+    pub fn new(threads: usize) -> Self {
+        WorkStealingExecutor { threads: threads.max(1), grain: 1 }
+    }
+
+    /// Tasks of this size or smaller aren't split any further, a piece has to be worth the bookkeeping.
+    pub fn grain(mut self, grain: usize) -> Self {
+        self.grain = grain.max(1);
+        self
+    }
+
+    /// `split` returns the two halves and the size of the first, or the item back if it's not to be split.
+    fn run<T, R>(
+        &self,
+        tasks: Vec<T>,
+        task: &(dyn Fn(T) -> R + Sync),
+        split: &(dyn Fn(T) -> Result<(T, T, usize), T> + Sync),
+        join: &(dyn Fn(R, R) -> R + Sync),
+    ) -> Vec<R>
+    where T: Send, R: Send,
+    {
+        let count = tasks.len();
+        let threads = self.threads.min(count).max(1);
+        let share = count.div_ceil(threads).max(1);
+
+        let mut deques = (0..threads).map(|_| VecDeque::new()).collect::<Vec<_>>();
+        for (index, item) in tasks.into_iter().enumerate() {
+            deques[index / share].push_back(Piece { task: index, offset: 0, item });
+        }
+        let shared = Deques {
+            deques: deques.into_iter().map(Mutex::new).collect(),
+            pending: AtomicUsize::new(count),
+            panicked: AtomicBool::new(false),
+            idle: Mutex::new(()),
+            wake: Condvar::new(),
+        };
+        let shared = &shared;
+
+        let outcome: Vec<thread::Result<Vec<(usize, usize, R)>>> = thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|worker| scope.spawn(move || {
+                    let mut done = Vec::new();
+                    let mut panic: Option<Box<dyn Any + Send>> = None;
+                    while !shared.panicked.load(Ordering::SeqCst) {
+                        let Some(Piece { task: index, offset, mut item }) = shared.next(worker) else {
+                            if shared.pending.load(Ordering::SeqCst) == 0 {
+                                break;
+                            }
+                            // somebody is still running or splitting a piece, more may turn up
+                            shared.wait_for_work();
+                            continue;
+                        };
+                        loop {
+                            match split(item) {
+                                Ok((first, second, first_size)) => {
+                                    shared.pending.fetch_add(1, Ordering::SeqCst);
+                                    shared.lock(worker).push_front(Piece { task: index, offset: offset + first_size, item: second });
+                                    shared.wake(false);
+                                    item = first;
+                                }
+                                Err(whole) => {
+                                    item = whole;
+                                    break;
+                                }
+                            }
+                        }
+                        match panic::catch_unwind(AssertUnwindSafe(|| task(item))) {
+                            Ok(result) => done.push((index, offset, result)),
+                            Err(payload) => {
+                                shared.panicked.store(true, Ordering::SeqCst);
+                                panic = Some(payload);
+                            }
+                        }
+                        if shared.pending.fetch_sub(1, Ordering::SeqCst) == 1 || panic.is_some() {
+                            shared.wake(true);
+                        }
+                    }
+                    match panic {
+                        Some(payload) => Err(payload),
+                        None => Ok(done),
+                    }
+                }))
+                .collect::<Vec<_>>();
+            workers.into_iter().map(|worker| worker.join().unwrap_or_else(Err)).collect()
+        });
+
+        let mut pieces = Vec::new();
+        for result in outcome {
+            pieces.extend(result.unwrap_or_else(|payload| panic::resume_unwind(payload)));
+        }
+
+        // the pieces of every task in order, folded back into one result
+        pieces.sort_unstable_by_key(|(task, offset, _)| (*task, *offset));
+        let mut results: Vec<(usize, R)> = Vec::with_capacity(count);
+        for (index, _, result) in pieces {
+            match results.pop() {
+                Some((last, joined)) if last == index => results.push((index, join(joined, result))),
+                previous => {
+                    results.extend(previous);
+                    results.push((index, result));
+                }
+            }
+        }
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+impl Executor for WorkStealingExecutor {
+
+    /// Stealing only, tasks which can't be split run whole.
+    fn execute<T, R, F>(&self, tasks: Vec<T>, task: F) -> Vec<R> where T: Send, R: Send, F: Fn(T) -> R + Sync {
+        self.run(tasks, &task, &Err, &|_, _| unreachable!("tasks are never split"))
+    }
+
+    fn execute_splittable<T, R, F, J>(&self, tasks: Vec<T>, task: F, join: J) -> Vec<R>
+    where T: Splittable + Send, R: Send, F: Fn(T) -> R + Sync, J: Fn(R, R) -> R + Sync,
+    {
+        let grain = self.grain;
+        let split = move |item: T| {
+            if item.size() <= grain {
+                return Err(item);
+            }
+            let (first, second) = item.split();
+            let first_size = first.size();
+            Ok((first, second, first_size))
+        };
+        self.run(tasks, &task, &split, &join)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::{BTreeMap, HashMap}, sync::Barrier, time::{Duration, Instant}};
+    use crate::{executor::{StaticSplitExecutor, ThreadExecutor}, map_reduce::MapReduce};
+    use super::*;
+
+    #[test]
+    fn split_pieces_join_back_in_order() {
+        let tasks = (0..5).map(|task| (task * 100..task * 100 + 37 * task).collect::<Vec<_>>()).collect::<Vec<_>>();
+        let joined = WorkStealingExecutor::new(3).grain(4).execute_splittable(tasks.clone(), |piece| piece, |mut a, b| {
+            a.extend(b);
+            a
+        });
+        assert_eq!(joined, tasks);
+    }
+
+    /// One big task next to three tiny ones, the 8 pieces of the big one wait for each other in fours,
+    /// so they only get through if all 4 threads stole some, 2 each.
+    #[test]
+    fn a_skewed_task_is_spread_over_every_thread() {
+        let tasks = vec![(0..64).collect::<Vec<u32>>(), vec![100], vec![101], vec![102]];
+        let barrier = Barrier::new(4);
+        let pieces = Mutex::new(HashMap::<_, usize>::new());
+        let joined = WorkStealingExecutor::new(4).grain(8).execute_splittable(tasks.clone(), |piece| {
+            if piece[0] < 100 {
+                barrier.wait();
+                *pieces.lock().unwrap().entry(thread::current().id()).or_default() += 1;
+            }
+            piece
+        }, |mut a, b| {
+            a.extend(b);
+            a
+        });
+        assert_eq!(joined, tasks);
+        assert_eq!(pieces.into_inner().unwrap().into_values().collect::<Vec<_>>(), [2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn a_panicking_task_stops_the_others() {
+        let result = panic::catch_unwind(|| {
+            WorkStealingExecutor::new(4).execute((0..1_000).collect(), |task: u32| {
+                if task == 10 {
+                    panic!("task 10");
+                }
+                thread::sleep(Duration::from_micros(100));
+            })
+        });
+        assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"task 10"));
+    }
+
+    /// A skewed map phase: 8 segments of 100 records, only the records of the first two are slow (a millisecond each,
+    /// slept like a slow lookup would be, so it shows the scheduling on any number of cores). With 4 threads:
+    /// - the static split gives both slow segments to the first thread, 200 slow records one after the other,
+    /// - the shared queue ([`ThreadExecutor`]) gets them on two threads, 100 each,
+    /// - work stealing splits them into pieces of 8 records, so all 4 threads end up with ~50 slow records.
+    #[test]
+    #[ignore = "benchmark, timing based: cargo test stealing_beats_a_static_split -- --ignored --nocapture"]
+    fn stealing_beats_a_static_split_on_skewed_segments() {
+        let job = MapReduce::fold(
+            |slow: bool, out| {
+                if slow {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                out.emit(slow, 1u32);
+            },
+            |a, b| a + b,
+        ).segment_size(100);
+        let records = || (0..800).map(|record| record < 200);
+
+        let time = |run: &dyn Fn() -> BTreeMap<bool, u32>| {
+            let started = Instant::now();
+            assert_eq!(run(), BTreeMap::from([(false, 600), (true, 200)]));
+            started.elapsed()
+        };
+        let static_split = time(&|| job.run_on(&StaticSplitExecutor::new(4), records()));
+        let shared_queue = time(&|| job.run_on(&ThreadExecutor::new(4), records()));
+        let stealing = time(&|| job.run_on(&WorkStealingExecutor::new(4).grain(8), records()));
+
+        // ~200ms, ~100ms and ~50ms of sleeping, far enough apart for a busy machine
+        assert!(static_split >= Duration::from_millis(200), "{:?}", static_split);
+        assert!(stealing < shared_queue && shared_queue < static_split, "{:?} {:?} {:?}", stealing, shared_queue, static_split);
+    }
+}
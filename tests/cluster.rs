@@ -0,0 +1,108 @@
+//! The map-reduce cluster across processes: this crate's binary as the coordinator and as its workers.
+
+use std::{collections::BTreeMap, io::{BufRead, BufReader, Read, Write}, process::{Child, ChildStdout, Command, Stdio}};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_lrn-rs");
+/// See `cluster::WORKER_COMMAND`.
+const WORKER: &str = "map-reduce-worker";
+/// See `cluster::flaky_word_count`.
+const STALL_AT: &str = "LRN_RS_STALL_AT";
+
+/// Kills the process when the test is done with it, or fails.
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// A worker for `job` at the coordinator `address`, with `vars` added to its environment.
+fn worker(address: &str, job: &str, vars: &[(&str, &str)]) -> Process {
+    let child = Command::new(BINARY)
+        .args([WORKER, address, job])
+        .envs(vars.iter().copied())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the worker starts");
+    Process(child)
+}
+
+/// `lrn-rs map-reduce <job>` on `lines`, with the address its workers connect to.
+fn coordinator(job: &str, lines: &[String]) -> (Process, String, BufReader<ChildStdout>) {
+    let mut child = Command::new(BINARY)
+        .args(["map-reduce", job])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the coordinator starts");
+    let mut stdin = child.stdin.take().expect("piped");
+    lines.iter().for_each(|line| writeln!(stdin, "{}", line).expect("the coordinator takes its input"));
+    drop(stdin);
+
+    let mut stdout = BufReader::new(child.stdout.take().expect("piped"));
+    let mut address = String::new();
+    stdout.read_line(&mut address).expect("the coordinator prints its address");
+    (Process(child), address.trim().to_string(), stdout)
+}
+
+/// The counts of every word in `lines`, split the way `map_reduce::words` does (they're lowercase already).
+fn word_count(lines: &[String]) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for word in lines.iter().flat_map(|line| line.split(|c: char| !c.is_alphanumeric() && c != '\'')).filter(|word| !word.is_empty()) {
+        *counts.entry(word.to_string()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// What `cluster::print_report` printed: the counts, and the `lost: ` lines.
+fn printed_report(printed: &str) -> (BTreeMap<String, u64>, Vec<&str>) {
+    let (lost, counts): (Vec<_>, Vec<_>) = printed.lines().partition(|line| line.starts_with("lost: "));
+    let counts = counts.iter()
+        .map(|line| line.split_once(' ').map(|(word, count)| (word.to_string(), count.parse::<u64>().unwrap())).unwrap())
+        .collect();
+    (counts, lost)
+}
+
+#[test]
+fn a_task_lost_with_its_killed_worker_goes_to_another() {
+    let lines = (0..500).map(|line| format!("line {} of segment {}", line % 7, line / 50)).collect::<Vec<_>>();
+    let expected = word_count(&lines);
+
+    let (mut coordinator, address, mut output) = coordinator("flaky_word_count", &lines);
+
+    // the only worker so far gets the first task, and hangs on its first record
+    let mut stalled = worker(&address, "flaky_word_count", &[(STALL_AT, "0")]);
+    let mut said = String::new();
+    BufReader::new(stalled.0.stderr.take().expect("piped")).read_line(&mut said).unwrap();
+    assert!(said.contains("stalls at record 0"), "{:?}", said);
+
+    // well before the coordinator's 10 second task timeout
+    let _healthy = worker(&address, "flaky_word_count", &[]);
+    stalled.0.kill().unwrap();
+
+    let mut printed = String::new();
+    output.read_to_string(&mut printed).unwrap();
+    assert!(coordinator.0.wait().unwrap().success(), "{}", printed);
+
+    let (counts, lost) = printed_report(&printed);
+    assert_eq!(counts, expected);
+    assert_eq!(lost.len(), 1, "{:?}", lost);
+    assert!(lost[0].starts_with("lost: segment 0 (attempt 1): worker lost: connection"), "{:?}", lost);
+}
+
+/// See `cluster::map_reduce_cluster_main`: the tasks of a crashed and a hanging worker go to the healthy one.
+#[test]
+fn the_cluster_demo_gets_over_a_crash_and_a_hang() {
+    let lines = (0..2_000).map(|line| format!("line {} of the map phase, segment {}", line % 7, line / 50)).collect::<Vec<_>>();
+    let output = Command::new(BINARY).arg("map-reduce-cluster").stderr(Stdio::null()).output().unwrap();
+    let printed = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", printed);
+
+    let (counts, lost) = printed_report(&printed);
+    assert_eq!(counts, word_count(&lines));
+    assert!(!lost.is_empty());
+    assert!(lost.iter().all(|line| line.contains("): worker lost: ")), "{:?}", lost);
+}